@@ -0,0 +1,84 @@
+//! ICC profile handling for re-encoded images.
+//!
+//! Wide-gamut screenshots (Display P3 on a Mac, an HDR monitor's profile on
+//! Windows) carry an ICC profile. Re-encoding without it makes viewers
+//! assume sRGB and the colors shift, so by default the profile is embedded
+//! in the output, or the pixels are converted to sRGB when the output
+//! format cannot hold one.
+
+use image::DynamicImage;
+use moxcms::{ColorProfile, Layout, TransformOptions};
+
+/// `WSL_CLIP_BRIDGE_ICC`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IccMode {
+    /// Embed the profile where the format allows, else convert to sRGB.
+    Keep,
+    /// Always convert to sRGB and embed nothing.
+    Srgb,
+    /// Drop the profile and leave the pixels alone.
+    Strip,
+}
+
+pub fn icc_mode() -> IccMode {
+    match crate::settings::setting("WSL_CLIP_BRIDGE_ICC")
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+        .as_str()
+    {
+        "srgb" => IccMode::Srgb,
+        "strip" | "none" => IccMode::Strip,
+        _ => IccMode::Keep,
+    }
+}
+
+/// Convert `img` from the color space described by `icc` to sRGB. Profiles
+/// that are not RGB (grayscale, CMYK) or fail to parse are reported as
+/// errors so the caller can fall back to the untouched pixels.
+pub fn to_srgb(img: &DynamicImage, icc: &[u8]) -> Result<DynamicImage, String> {
+    let source =
+        ColorProfile::new_from_slice(icc).map_err(|e| format!("ICC profile unreadable: {e}"))?;
+    let transform = source
+        .create_transform_8bit(
+            Layout::Rgba,
+            &ColorProfile::new_srgb(),
+            Layout::Rgba,
+            TransformOptions::default(),
+        )
+        .map_err(|e| format!("ICC transform failed: {e}"))?;
+    let rgba = img.to_rgba8();
+    let mut out = image::RgbaImage::new(rgba.width(), rgba.height());
+    transform
+        .transform(rgba.as_raw(), &mut out)
+        .map_err(|e| format!("ICC transform failed: {e}"))?;
+    Ok(DynamicImage::ImageRgba8(out))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srgb_to_srgb_is_near_identity() {
+        let icc = ColorProfile::new_srgb()
+            .encode()
+            .expect("encode sRGB profile");
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            2,
+            2,
+            image::Rgba([200, 100, 50, 255]),
+        ));
+        let out = to_srgb(&img, &icc).expect("transform").to_rgba8();
+        let px = out.get_pixel(1, 1).0;
+        for (got, want) in px.iter().zip([200u8, 100, 50, 255]) {
+            assert!(got.abs_diff(want) <= 2, "{px:?}");
+        }
+    }
+
+    #[test]
+    fn garbage_profile_is_an_error() {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::new(1, 1));
+        assert!(to_srgb(&img, b"not a profile").is_err());
+    }
+}
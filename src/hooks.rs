@@ -0,0 +1,149 @@
+//! User hook commands, run after a copy or paste.
+//!
+//! `WSL_CLIP_BRIDGE_ON_COPY` / `WSL_CLIP_BRIDGE_ON_PASTE` hold a shell
+//! command. It sees the event, MIME type, selection, and a private temp
+//! file with the payload in its environment, and is killed if it outlives
+//! the hook timeout. A failing hook is reported but never fails the copy or
+//! paste itself.
+
+use std::env;
+use std::ffi::OsStr;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::Selection;
+use crate::process::Cmd;
+
+const HOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    Copy,
+    Paste,
+}
+
+impl Event {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Copy => "copy",
+            Self::Paste => "paste",
+        }
+    }
+
+    const fn variable(self) -> &'static str {
+        match self {
+            Self::Copy => "WSL_CLIP_BRIDGE_ON_COPY",
+            Self::Paste => "WSL_CLIP_BRIDGE_ON_PASTE",
+        }
+    }
+}
+
+pub fn configured(event: Event) -> bool {
+    command(event).is_some()
+}
+
+fn command(event: Event) -> Option<String> {
+    crate::settings::setting(event.variable()).filter(|c| !c.trim().is_empty())
+}
+
+/// Run the hook configured for `event`, if any, and wait for it.
+pub fn run(event: Event, mime: &str, sel: Selection, data: &[u8]) {
+    let Some(command) = command(event) else {
+        return;
+    };
+    let timeout = crate::settings::env_u64("WSL_CLIP_BRIDGE_HOOK_TIMEOUT_MS")
+        .map_or(HOOK_TIMEOUT, Duration::from_millis);
+    if let Err(e) = run_command(&command, event, mime, sel, data, timeout) {
+        eprintln!("xclip: {} hook failed: {e}", event.name());
+    }
+}
+
+fn run_command(
+    command: &str,
+    event: Event,
+    mime: &str,
+    sel: Selection,
+    data: &[u8],
+    timeout: Duration,
+) -> io::Result<()> {
+    let file = TempFile::create(data)?;
+    // Output goes to /dev/null: on paste our stdout is the payload, and a
+    // job the hook backgrounds must not hold pipes we wait on.
+    Cmd::new("sh", timeout)
+        .args(&["-c", command])
+        .env("WSL_CLIP_BRIDGE_EVENT", OsStr::new(event.name()))
+        .env("WSL_CLIP_BRIDGE_MIME", OsStr::new(mime))
+        .env("WSL_CLIP_BRIDGE_SELECTION", OsStr::new(sel.name()))
+        .env("WSL_CLIP_BRIDGE_FILE", file.0.as_os_str())
+        .discard_output()
+        .run()
+        .map(drop)
+}
+
+/// The payload handed to a hook: owner-only, removed once the hook returns.
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn create(data: &[u8]) -> io::Result<Self> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let path = temp_dir().join(format!(
+            "wsl-clip-bridge-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)?;
+        let temp = Self(path);
+        file.write_all(data)?;
+        Ok(temp)
+    }
+}
+
+/// The per-user runtime directory when there is one: unlike `/tmp`, other
+/// users cannot list it or squat on the predictable file name.
+fn temp_dir() -> PathBuf {
+    env::var_os("XDG_RUNTIME_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_dir())
+        .unwrap_or_else(env::temp_dir)
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    #[test]
+    fn backgrounded_hooks_return_within_the_timeout() {
+        let start = Instant::now();
+        let result = run_command(
+            "sleep 5 & echo started",
+            Event::Paste,
+            "text/plain",
+            Selection::Clipboard,
+            b"hi",
+            Duration::from_millis(300),
+        );
+        assert!(result.is_ok(), "{result:?}");
+        assert!(
+            start.elapsed() < Duration::from_secs(2),
+            "{:?}",
+            start.elapsed()
+        );
+    }
+}
@@ -0,0 +1,582 @@
+//! Image conversion: BMP and TIFF to PNG, transcoding between formats,
+//! thumbnails, and shrinking to a byte limit. Built with the `image`
+//! feature; without it, images are only passed through.
+
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageDecoder, ImageEncoder, ImageFormat, ImageReader};
+
+use crate::settings::{env_bool, env_u64, setting};
+use crate::{
+    Selection, base_mime, color, fetch, list_types, mime_matches, parse_hex_color, passthrough,
+    same_base_mime,
+};
+
+/// The clipboard image as PNG: passed through if offered, else converted
+/// from BMP/TIFF, rendered from SVG, or transcoded from another format.
+pub fn clipboard_png(sel: Selection) -> Result<Vec<u8>, String> {
+    fetch("image/png", sel)
+        .or_else(|_| bitmap_to_png(sel))
+        .or_else(|err| svg_to_png(sel).unwrap_or(Err(err)))
+        .or_else(|err| transcode(sel, ImageFormat::Png).unwrap_or(Err(err)))
+}
+
+/// Any other raster type: passed through when offered, otherwise
+/// transcoded from whatever image the clipboard does hold, since Electron
+/// apps ask for one exact type.
+pub fn image_as(mime: &str, sel: Selection) -> Result<Vec<u8>, String> {
+    passthrough(mime, sel).or_else(|err| {
+        ImageFormat::from_mime_type(base_mime(mime))
+            .and_then(|format| transcode(sel, format))
+            .unwrap_or(Err(err))
+    })
+}
+
+/// Default longest side of `image/x-thumbnail`.
+const THUMBNAIL_SIDE: u32 = 256;
+
+/// `image/x-thumbnail`: a small JPEG of the clipboard image, so picker
+/// preview panes do not have to decode a multi-megabyte screenshot. Sized
+/// by `WSL_CLIP_BRIDGE_THUMBNAIL_SIZE`; smaller images are not enlarged.
+pub fn thumbnail(sel: Selection) -> Result<Vec<u8>, String> {
+    let side = env_u64("WSL_CLIP_BRIDGE_THUMBNAIL_SIZE")
+        .and_then(|n| u32::try_from(n).ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(THUMBNAIL_SIDE);
+    let mut decoded = decode(&clipboard_png(sel)?, Some(ImageFormat::Png))?;
+    if decoded.img.width().max(decoded.img.height()) > side {
+        decoded.img = decoded.img.thumbnail(side, side);
+    }
+    encode_image(&decoded, ImageFormat::Jpeg)
+}
+
+/// Smallest side [`shrink_image`] will scale an image down to.
+const MIN_SHRINK_SIDE: u32 = 64;
+
+/// Apply `WSL_CLIP_BRIDGE_MAX_IMAGE_BYTES` to an image about to be output.
+pub fn fit_image(data: Vec<u8>, mime: &str) -> Vec<u8> {
+    let limit = setting("WSL_CLIP_BRIDGE_MAX_IMAGE_BYTES")
+        .and_then(|spec| byte_limit(&spec, mime))
+        .filter(|&n| n > 0)
+        .and_then(|n| usize::try_from(n).ok());
+    fit_to_limit(data, mime, limit)
+}
+
+fn fit_to_limit(data: Vec<u8>, mime: &str, limit: Option<usize>) -> Vec<u8> {
+    let format = match base_mime(mime) {
+        "image/jpg" => Some(ImageFormat::Jpeg),
+        "image/svg+xml" => None,
+        m => ImageFormat::from_mime_type(m),
+    };
+    match (limit, format) {
+        // Shrinking decodes a single frame, which would stop an animation.
+        (Some(limit), Some(format)) if data.len() > limit && is_animated(&data, format) => {
+            eprintln!(
+                "xclip: not shrinking an animated {mime}, sending all {} bytes",
+                data.len()
+            );
+            data
+        }
+        (Some(limit), Some(format)) if data.len() > limit => {
+            let shrunk = shrink_image(data, format, limit);
+            if shrunk.len() > limit {
+                eprintln!(
+                    "xclip: could not get the image under {limit} bytes, sending {}",
+                    shrunk.len()
+                );
+            }
+            shrunk
+        }
+        _ => data,
+    }
+}
+
+/// Whether `data` is a GIF or WebP with more than one frame.
+fn is_animated(data: &[u8], format: ImageFormat) -> bool {
+    match format {
+        ImageFormat::Gif => is_animated_gif(data),
+        // An extended (VP8X) header with the animation flag set.
+        ImageFormat::WebP => {
+            data.get(12..16) == Some(b"VP8X") && data.get(20).is_some_and(|flags| flags & 0x02 != 0)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(feature = "gif")]
+fn is_animated_gif(data: &[u8]) -> bool {
+    use image::AnimationDecoder;
+    image::codecs::gif::GifDecoder::new(Cursor::new(data))
+        .is_ok_and(|decoder| decoder.into_frames().take(2).count() > 1)
+}
+
+/// Built without the `gif` feature: GIFs cannot be decoded, so are never
+/// shrunk either.
+#[cfg(not(feature = "gif"))]
+const fn is_animated_gif(_data: &[u8]) -> bool {
+    false
+}
+
+/// The limit for `mime` in a `WSL_CLIP_BRIDGE_MAX_IMAGE_BYTES` value: a
+/// comma-separated list of `type=bytes` overrides (`type/*` allowed) and at
+/// most one bare default, e.g. `2000000,image/webp=0`.
+fn byte_limit(spec: &str, mime: &str) -> Option<u64> {
+    let mut default = None;
+    for entry in spec.split(',').map(str::trim) {
+        match entry.split_once('=') {
+            Some((pattern, bytes)) if mime_matches(pattern.trim(), mime) => {
+                return bytes.trim().parse().ok();
+            }
+            Some(_) => {}
+            None => default = entry.parse().ok().or(default),
+        }
+    }
+    default
+}
+
+/// Re-encode `data` with the configured compression, then scale it down by
+/// a quarter at a time until it fits in `limit` bytes or reaches
+/// [`MIN_SHRINK_SIDE`]. Returns the smallest encoding produced.
+fn shrink_image(data: Vec<u8>, format: ImageFormat, limit: usize) -> Vec<u8> {
+    let Ok(mut decoded) = decode(&data, Some(format)) else {
+        return data;
+    };
+    let mut best = data;
+    loop {
+        match encode_image(&decoded, format) {
+            Ok(out) if out.len() < best.len() => best = out,
+            Ok(_) => {}
+            Err(_) => break,
+        }
+        let (width, height) = (decoded.img.width(), decoded.img.height());
+        if best.len() <= limit || width.max(height) <= MIN_SHRINK_SIDE {
+            break;
+        }
+        decoded.img = decoded.img.resize(
+            (width * 3 / 4).max(1),
+            (height * 3 / 4).max(1),
+            image::imageops::FilterType::Triangle,
+        );
+    }
+    best
+}
+
+/// Source types for [`transcode`], lossless ones first.
+const TRANSCODE_SOURCES: [(&str, ImageFormat); 6] = [
+    ("image/png", ImageFormat::Png),
+    ("image/bmp", ImageFormat::Bmp),
+    ("image/tiff", ImageFormat::Tiff),
+    ("image/webp", ImageFormat::WebP),
+    ("image/jpeg", ImageFormat::Jpeg),
+    ("image/gif", ImageFormat::Gif),
+];
+
+/// Re-encode the clipboard image as `format`. `None` when transcoding is
+/// off (`WSL_CLIP_BRIDGE_TRANSCODE=0`), `format` cannot be written by this
+/// build, or the clipboard holds no image this build can decode.
+fn transcode(sel: Selection, format: ImageFormat) -> Option<Result<Vec<u8>, String>> {
+    if env_bool("WSL_CLIP_BRIDGE_TRANSCODE") == Some(false) || !format.writing_enabled() {
+        return None;
+    }
+    let types = list_types(sel).ok()?;
+    let offered = |m| types.iter().any(|t| same_base_mime(t, m));
+    let source = match TRANSCODE_SOURCES
+        .iter()
+        .find(|(m, f)| f.reading_enabled() && offered(m))
+    {
+        Some(&(mime, source_format)) => {
+            passthrough(mime, sel).and_then(|data| decode(&data, Some(source_format)))
+        }
+        None => svg_to_png(sel)?.and_then(|png| decode(&png, Some(ImageFormat::Png))),
+    };
+    Some(source.and_then(|decoded| encode_image(&decoded, format)))
+}
+
+/// A decoded image and the ICC profile it was tagged with, if any.
+pub struct Decoded {
+    pub img: DynamicImage,
+    pub icc: Option<Vec<u8>>,
+}
+
+/// Decode `data` as `format`, or whatever its magic bytes say.
+/// Refuses images larger than the limits before their pixels are allocated,
+/// so a crafted header cannot exhaust memory.
+fn decode(data: &[u8], format: Option<ImageFormat>) -> Result<Decoded, String> {
+    let mut reader = match format {
+        Some(format) => ImageReader::with_format(Cursor::new(data), format),
+        None => ImageReader::new(Cursor::new(data))
+            .with_guessed_format()
+            .map_err(|e| format!("image decode failed: {e}"))?,
+    };
+    reader.limits(decode_limits());
+    let mut decoder = reader.into_decoder().map_err(|e| decode_error(&e))?;
+    let icc = decoder.icc_profile().ok().flatten();
+    let img = DynamicImage::from_decoder(decoder).map_err(|e| decode_error(&e))?;
+    Ok(Decoded { img, icc })
+}
+
+/// Longest side [`decode`] accepts; real screenshots stay far below it.
+const MAX_DECODE_SIDE: u32 = 16_384;
+
+/// Dimension cap plus the decoder memory budget
+/// (`WSL_CLIP_BRIDGE_DECODE_LIMIT_MB`, default 512).
+fn decode_limits() -> image::Limits {
+    let mut limits = image::Limits::default();
+    limits.max_image_width = Some(MAX_DECODE_SIDE);
+    limits.max_image_height = Some(MAX_DECODE_SIDE);
+    if let Some(mb) = env_u64("WSL_CLIP_BRIDGE_DECODE_LIMIT_MB") {
+        limits.max_alloc = Some(mb.saturating_mul(1024 * 1024));
+    }
+    limits
+}
+
+fn decode_error(e: &image::ImageError) -> String {
+    if matches!(e, image::ImageError::Limits(_)) {
+        format!("image too large to decode ({e}); see WSL_CLIP_BRIDGE_DECODE_LIMIT_MB")
+    } else {
+        format!("image decode failed: {e}")
+    }
+}
+
+/// Composite `img` over an opaque `background`, for formats without an
+/// alpha channel. Dropping alpha instead would turn every transparent pixel
+/// black.
+fn flatten(img: &DynamicImage, background: [u8; 3]) -> DynamicImage {
+    let mut rgb = image::RgbImage::new(img.width(), img.height());
+    for (out, px) in rgb.pixels_mut().zip(img.to_rgba8().pixels()) {
+        let alpha = u16::from(px[3]);
+        for c in 0..3 {
+            let blended =
+                (u16::from(px[c]) * alpha + u16::from(background[c]) * (255 - alpha) + 127) / 255;
+            out[c] = u8::try_from(blended).unwrap_or(u8::MAX);
+        }
+    }
+    DynamicImage::ImageRgb8(rgb)
+}
+
+/// Background for [`flatten`] (`WSL_CLIP_BRIDGE_BACKGROUND`, `#rrggbb`;
+/// white by default, like a document the screenshot gets pasted into).
+fn background() -> [u8; 3] {
+    setting("WSL_CLIP_BRIDGE_BACKGROUND")
+        .and_then(|v| parse_hex_color(&v))
+        .unwrap_or([255, 255, 255])
+}
+
+/// `WSL_CLIP_BRIDGE_JPEG_QUALITY`, 1 to 100 (default 75).
+fn jpeg_quality() -> u8 {
+    env_u64("WSL_CLIP_BRIDGE_JPEG_QUALITY")
+        .and_then(|q| u8::try_from(q.clamp(1, 100)).ok())
+        .unwrap_or(75)
+}
+
+/// `WSL_CLIP_BRIDGE_PNG_COMPRESSION`: `fast` (default), `default`, `best`,
+/// or a zlib level from 0 to 9.
+fn png_compression() -> image::codecs::png::CompressionType {
+    use image::codecs::png::CompressionType;
+    let value = setting("WSL_CLIP_BRIDGE_PNG_COMPRESSION").unwrap_or_default();
+    match value.trim().to_ascii_lowercase().as_str() {
+        "default" => CompressionType::Default,
+        "best" => CompressionType::Best,
+        "0" => CompressionType::Uncompressed,
+        level => level
+            .parse::<u8>()
+            .ok()
+            .filter(|l| (1..=9).contains(l))
+            .map_or(CompressionType::Fast, CompressionType::Level),
+    }
+}
+
+/// Encode as `format`, handling the source's ICC profile per
+/// [`color::icc_mode`]: embedded where the format can hold it, otherwise
+/// baked into the pixels by converting to sRGB.
+pub fn encode_image(decoded: &Decoded, format: ImageFormat) -> Result<Vec<u8>, String> {
+    let mode = color::icc_mode();
+    let icc = decoded
+        .icc
+        .as_deref()
+        .filter(|_| mode != color::IccMode::Strip);
+    let embeddable = matches!(
+        format,
+        ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::WebP | ImageFormat::Tiff
+    );
+    let converted;
+    let (img, embed) = match icc {
+        Some(profile) if mode == color::IccMode::Srgb || !embeddable => {
+            match color::to_srgb(&decoded.img, profile) {
+                Ok(srgb) => {
+                    converted = srgb;
+                    (&converted, None)
+                }
+                Err(e) => {
+                    eprintln!("xclip: keeping the original colors: {e}");
+                    (&decoded.img, None)
+                }
+            }
+        }
+        embed => (&decoded.img, embed),
+    };
+
+    let mut buf = Cursor::new(Vec::new());
+    let result = match format {
+        ImageFormat::Png => img.write_with_encoder(with_icc(
+            image::codecs::png::PngEncoder::new_with_quality(
+                &mut buf,
+                png_compression(),
+                image::codecs::png::FilterType::Adaptive,
+            ),
+            embed,
+        )),
+        ImageFormat::Jpeg => flatten(img, background()).write_with_encoder(with_icc(
+            image::codecs::jpeg::JpegEncoder::new_with_quality(&mut buf, jpeg_quality()),
+            embed,
+        )),
+        #[cfg(feature = "webp")]
+        ImageFormat::WebP => img.write_with_encoder(with_icc(
+            image::codecs::webp::WebPEncoder::new_lossless(&mut buf),
+            embed,
+        )),
+        #[cfg(feature = "tiff")]
+        ImageFormat::Tiff => img.write_with_encoder(with_icc(
+            image::codecs::tiff::TiffEncoder::new(&mut buf),
+            embed,
+        )),
+        // Speed 8 of 10 keeps a screenshot encode well under a second.
+        #[cfg(feature = "avif")]
+        ImageFormat::Avif => img.write_with_encoder(
+            image::codecs::avif::AvifEncoder::new_with_speed_quality(&mut buf, 8, 80),
+        ),
+        _ => img.write_to(&mut buf, format),
+    };
+    result.map_err(|e| format!("{format:?} encode failed: {e}"))?;
+    Ok(buf.into_inner())
+}
+
+fn with_icc<E: ImageEncoder>(mut encoder: E, icc: Option<&[u8]>) -> E {
+    if let Some(icc) = icc {
+        // Only fails for formats that cannot hold a profile, which
+        // encode_image() converts instead.
+        let _ = encoder.set_icc_profile(icc.to_vec());
+    }
+    encoder
+}
+
+/// Convert the offered BMP, or failing that a TIFF (what `ShareX` and some
+/// scanners put on the clipboard), to PNG.
+fn bitmap_to_png(sel: Selection) -> Result<Vec<u8>, String> {
+    let bitmap = fetch("image/bmp", sel)
+        .or_else(|err| {
+            let types = list_types(sel).unwrap_or_default();
+            if ImageFormat::Tiff.reading_enabled()
+                && types.iter().any(|t| same_base_mime(t, "image/tiff"))
+            {
+                fetch("image/tiff", sel)
+            } else {
+                Err(err)
+            }
+        })
+        .map_err(|e| format!("reading image/bmp failed: {e}"))?;
+    encode_png(&bitmap)
+}
+
+/// Rasterize an offered SVG; `None` if there is none to render.
+#[cfg(feature = "svg")]
+fn svg_to_png(sel: Selection) -> Option<Result<Vec<u8>, String>> {
+    let types = list_types(sel).ok()?;
+    types.iter().find(|t| same_base_mime(t, "image/svg+xml"))?;
+    let size = env_u64("WSL_CLIP_BRIDGE_SVG_SIZE").map(|n| u16::try_from(n).unwrap_or(u16::MAX));
+    Some(passthrough("image/svg+xml", sel).and_then(|data| crate::svg::rasterize(&data, size)))
+}
+
+/// Built without the `svg` feature: SVGs are only passed through.
+#[cfg(not(feature = "svg"))]
+const fn svg_to_png(_sel: Selection) -> Option<Result<Vec<u8>, String>> {
+    None
+}
+
+/// Re-encode a BMP or TIFF as PNG.
+pub fn encode_png(bitmap: &[u8]) -> Result<Vec<u8>, String> {
+    encode_image(&decode(bitmap, None)?, ImageFormat::Png)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "tiff")]
+    fn tiff_converts_to_png() {
+        let mut tiff = Cursor::new(Vec::new());
+        image::RgbImage::from_pixel(3, 2, image::Rgb([0, 0, 255]))
+            .write_to(&mut tiff, ImageFormat::Tiff)
+            .expect("encode TIFF");
+        let png = encode_png(tiff.get_ref()).expect("convert");
+        let img = image::load_from_memory_with_format(&png, ImageFormat::Png).expect("decode");
+        assert_eq!((img.width(), img.height()), (3, 2));
+    }
+
+    #[test]
+    fn images_transcode_between_formats() {
+        let img = Decoded {
+            img: DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+                8,
+                8,
+                image::Rgba([0, 128, 255, 128]),
+            )),
+            icc: None,
+        };
+        let formats = [
+            ImageFormat::Jpeg,
+            ImageFormat::WebP,
+            ImageFormat::Gif,
+            ImageFormat::Avif,
+        ];
+        for format in formats.into_iter().filter(ImageFormat::writing_enabled) {
+            let data = encode_image(&img, format).expect("encode");
+            assert_eq!(image::guess_format(&data).ok(), Some(format));
+        }
+    }
+
+    #[test]
+    fn icc_profile_is_carried_through() {
+        let icc = moxcms::ColorProfile::new_display_p3()
+            .encode()
+            .expect("encode profile");
+        let img = Decoded {
+            img: DynamicImage::ImageRgb8(image::RgbImage::from_pixel(
+                2,
+                2,
+                image::Rgb([60, 160, 90]),
+            )),
+            icc: Some(icc.clone()),
+        };
+        let formats = [ImageFormat::Png, ImageFormat::Jpeg, ImageFormat::WebP];
+        for format in formats.into_iter().filter(ImageFormat::writing_enabled) {
+            let data = encode_image(&img, format).expect("encode");
+            assert_eq!(
+                decode(&data, None).expect("decode").icc.as_ref(),
+                Some(&icc),
+                "{format:?}"
+            );
+        }
+        if !ImageFormat::Gif.writing_enabled() {
+            return;
+        }
+        // GIF cannot hold a profile: the P3 pixels are converted to sRGB instead.
+        let gif = encode_image(&img, ImageFormat::Gif).expect("encode");
+        let decoded = decode(&gif, None).expect("decode");
+        assert_eq!(decoded.icc, None);
+        assert_ne!(decoded.img.to_rgb8().get_pixel(0, 0).0, [60, 160, 90]);
+    }
+
+    #[test]
+    fn oversized_images_are_shrunk_to_the_limit() {
+        // Noise barely compresses, so only downscaling gets it small.
+        let mut seed = 1u32;
+        let noise = image::RgbImage::from_fn(256, 256, |_, _| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let [r, g, b, _] = seed.to_be_bytes();
+            image::Rgb([r, g, b])
+        });
+        let png = encode_image(
+            &Decoded {
+                img: DynamicImage::ImageRgb8(noise),
+                icc: None,
+            },
+            ImageFormat::Png,
+        )
+        .expect("encode");
+        let limit = png.len() / 4;
+        let shrunk = shrink_image(png, ImageFormat::Png, limit);
+        assert!(shrunk.len() <= limit, "{} > {limit}", shrunk.len());
+        let img = image::load_from_memory(&shrunk).expect("decode");
+        assert!(img.width() < 256 && img.width() >= MIN_SHRINK_SIDE);
+    }
+
+    #[test]
+    #[cfg(feature = "gif")]
+    fn animations_are_not_shrunk() {
+        let mut gif = Vec::new();
+        let frames = (0..4u8).map(|i| {
+            image::Frame::new(image::RgbaImage::from_pixel(
+                64,
+                64,
+                image::Rgba([i * 60, 0, 0, 255]),
+            ))
+        });
+        image::codecs::gif::GifEncoder::new(&mut gif)
+            .encode_frames(frames)
+            .expect("encode GIF");
+        assert!(is_animated(&gif, ImageFormat::Gif));
+        let out = fit_to_limit(gif.clone(), "image/gif", Some(gif.len() / 4));
+        assert!(out == gif, "{} of {} bytes", out.len(), gif.len());
+        let mut webp = b"RIFF\0\0\0\0WEBPVP8X\x0a\0\0\0\x02".to_vec();
+        assert!(is_animated(&webp, ImageFormat::WebP));
+        webp[20] = 0;
+        assert!(!is_animated(&webp, ImageFormat::WebP));
+    }
+
+    #[test]
+    fn byte_limits_can_be_set_per_type() {
+        let spec = "2000000, image/webp=0, image/jpeg=5";
+        assert_eq!(byte_limit(spec, "image/png"), Some(2_000_000));
+        assert_eq!(byte_limit(spec, "image/webp"), Some(0));
+        assert_eq!(byte_limit("image/*=7,9", "image/gif"), Some(7));
+        assert_eq!(byte_limit("image/png=7", "image/gif"), None);
+        assert_eq!(byte_limit("300", "image/gif"), Some(300));
+    }
+
+    #[test]
+    fn oversized_headers_are_refused() {
+        let mut bmp = crate::tests::tiny_bmp();
+        bmp[18..22].copy_from_slice(&30_000i32.to_le_bytes());
+        bmp[22..26].copy_from_slice(&30_000i32.to_le_bytes());
+        let err = decode(&bmp, None).err().expect("refused");
+        assert!(err.starts_with("image too large"), "{err}");
+    }
+
+    #[test]
+    fn transparency_is_flattened_onto_the_background() {
+        let img = image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(2, 1, |x, _| {
+            if x == 0 {
+                image::Rgba([0, 0, 0, 0])
+            } else {
+                image::Rgba([255, 0, 0, 128])
+            }
+        }));
+        let flat = flatten(&img, [0, 0, 255]).to_rgb8();
+        assert_eq!(flat.get_pixel(0, 0).0, [0, 0, 255]);
+        assert_eq!(flat.get_pixel(1, 0).0, [128, 0, 127]);
+    }
+
+    #[test]
+    fn bmp_round_trips_to_png() {
+        let bmp = crate::tests::tiny_bmp();
+        assert_eq!(&bmp[0..2], b"BM");
+        let img = image::load_from_memory(&bmp).expect("load BMP");
+        let mut out = Cursor::new(Vec::new());
+        img.write_to(&mut out, ImageFormat::Png)
+            .expect("encode PNG");
+        let png = out.into_inner();
+        assert_eq!(
+            &png[0..8],
+            &[0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A]
+        );
+    }
+
+    #[test]
+    fn bitfields_bmp_round_trips_to_png() {
+        let bmp = crate::selftest::bitfields_bmp();
+        assert_eq!(&bmp[0..2], b"BM");
+        let img = image::load_from_memory(&bmp).expect("load BI_BITFIELDS BMP");
+        assert_eq!(img.width(), 2);
+        assert_eq!(img.height(), 1);
+        let mut out = Cursor::new(Vec::new());
+        img.write_to(&mut out, ImageFormat::Png)
+            .expect("encode PNG");
+        let png = out.into_inner();
+        assert_eq!(
+            &png[0..8],
+            &[0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A]
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! `--info [--json]`: what the selection holds right now, type by type, for
+//! working out why a paste did not do what was expected.
+
+use std::fmt::Write as _;
+#[cfg(feature = "image")]
+use std::io::Cursor;
+
+#[cfg(feature = "image")]
+use image::{ImageFormat, ImageReader};
+
+#[cfg(feature = "image")]
+use crate::base_mime;
+use crate::{Selection, fetch, list_types, sensitive, wsl};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    /// `--json`
+    Json,
+}
+
+/// One offered type.
+struct Entry {
+    mime: String,
+    /// Size and fingerprint, or why the type could not be read.
+    content: Result<(usize, Option<u64>), String>,
+    dimensions: Option<(u32, u32)>,
+}
+
+pub fn run(sel: Selection, format: Format) -> i32 {
+    let types = match list_types(sel) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("xclip: listing clipboard types failed: {e}");
+            return 1;
+        }
+    };
+    // Never fingerprint a password manager's copy: a hash of a short secret
+    // can be brute-forced.
+    let secret = sensitive::is_marked(sel);
+    let entries: Vec<Entry> = types
+        .into_iter()
+        .map(|mime| {
+            let data = fetch(&mime, sel);
+            let dimensions = data.as_ref().ok().and_then(|d| dimensions(&mime, d));
+            let content = data
+                .map(|d| (d.len(), (!secret).then(|| sensitive::fingerprint(&d))))
+                .map_err(|e| e.to_string());
+            Entry {
+                mime,
+                content,
+                dimensions,
+            }
+        })
+        .collect();
+    let text = match format {
+        Format::Text => render_text(sel, &backend(), secret, &entries),
+        Format::Json => render_json(sel, &backend(), secret, &entries),
+    };
+    print!("{text}");
+    0
+}
+
+/// Which backend answers, and where it was found.
+fn backend() -> String {
+    #[cfg(feature = "x11")]
+    if wsl::prefer_x11() {
+        return "x11".to_owned();
+    }
+    wsl::environment().wayland_socket.as_deref().map_or_else(
+        || "wayland".to_owned(),
+        |p| format!("wayland ({})", p.display()),
+    )
+}
+
+/// Read from the header only; the pixels are never decoded.
+#[cfg(feature = "image")]
+fn dimensions(mime: &str, data: &[u8]) -> Option<(u32, u32)> {
+    let format = ImageFormat::from_mime_type(base_mime(mime))?;
+    ImageReader::with_format(Cursor::new(data), format)
+        .into_dimensions()
+        .ok()
+}
+
+#[cfg(not(feature = "image"))]
+const fn dimensions(_mime: &str, _data: &[u8]) -> Option<(u32, u32)> {
+    None
+}
+
+fn render_text(sel: Selection, backend: &str, secret: bool, entries: &[Entry]) -> String {
+    let mut out = format!(
+        "selection: {}\nbackend: {backend}\nsensitive: {}\n",
+        sel.name(),
+        if secret { "yes" } else { "no" }
+    );
+    for entry in entries {
+        let _ = write!(out, "{}", entry.mime);
+        match &entry.content {
+            Ok((len, hash)) => {
+                let _ = write!(out, "  {len} bytes");
+                if let Some((w, h)) = entry.dimensions {
+                    let _ = write!(out, "  {w}x{h}");
+                }
+                if let Some(hash) = hash {
+                    let _ = write!(out, "  hash {hash:016x}");
+                }
+            }
+            Err(e) => {
+                let _ = write!(out, "  unreadable: {e}");
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn render_json(sel: Selection, backend: &str, secret: bool, entries: &[Entry]) -> String {
+    let types: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+            let mut fields = vec![format!("\"mime\":{}", quote(&entry.mime))];
+            match &entry.content {
+                Ok((len, hash)) => {
+                    fields.push(format!("\"bytes\":{len}"));
+                    if let Some((w, h)) = entry.dimensions {
+                        fields.push(format!("\"width\":{w},\"height\":{h}"));
+                    }
+                    if let Some(hash) = hash {
+                        fields.push(format!("\"hash\":\"{hash:016x}\""));
+                    }
+                }
+                Err(e) => fields.push(format!("\"error\":{}", quote(e))),
+            }
+            format!("{{{}}}", fields.join(","))
+        })
+        .collect();
+    format!(
+        "{{\"selection\":{},\"backend\":{},\"sensitive\":{secret},\"types\":[{}]}}\n",
+        quote(sel.name()),
+        quote(backend),
+        types.join(",")
+    )
+}
+
+/// A JSON string literal.
+fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if u32::from(c) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", u32::from(c));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_output_is_well_formed() {
+        let entries = [
+            Entry {
+                mime: "image/bmp".to_owned(),
+                content: Ok((58, Some(0xabc))),
+                dimensions: Some((2, 1)),
+            },
+            Entry {
+                mime: "text/plain".to_owned(),
+                content: Err("said \"no\"\n".to_owned()),
+                dimensions: None,
+            },
+        ];
+        assert_eq!(
+            render_json(Selection::Primary, "x11", false, &entries),
+            "{\"selection\":\"primary\",\"backend\":\"x11\",\"sensitive\":false,\"types\":[\
+             {\"mime\":\"image/bmp\",\"bytes\":58,\"width\":2,\"height\":1,\"hash\":\"0000000000000abc\"},\
+             {\"mime\":\"text/plain\",\"error\":\"said \\\"no\\\"\\n\"}]}\n"
+        );
+        assert_eq!(quote("\u{1}"), "\"\\u0001\"");
+    }
+}
@@ -1,19 +1,43 @@
 //! wsl-clip-bridge
 //!
 //! A stateless `xclip` shim for Claude Code on WSL. Shells out to `wl-paste`
-//! for the Windows clipboard and, when the payload is a `BI_BITFIELDS` BMP
+//! (and `wl-copy` for `-i`) for the Windows clipboard and, when the payload is a `BI_BITFIELDS` BMP
 //! (which sharp/libvips refuses), decodes it via the Rust `image` crate and
 //! emits a PNG. That is the only reason this tool exists: Claude Code's paste
 //! pipeline otherwise silently fails on WSLg-sourced screenshots.
 
+#[cfg(feature = "image")]
+mod color;
+mod hooks;
+#[cfg(feature = "image")]
+mod images;
+mod info;
+mod ocr;
+mod osc52;
+mod process;
+mod profile;
+mod selftest;
+mod sensitive;
+mod settings;
+#[cfg(feature = "svg")]
+mod svg;
+mod text;
+mod wsl;
+#[cfg(feature = "x11")]
+mod x11;
+
 use std::env;
-use std::io::{self, Cursor, Read, Write};
-use std::process::{Command, ExitCode};
-use std::sync::mpsc;
-use std::thread;
-use std::time::{Duration, Instant};
+use std::io::{self, Read, Write};
+use std::process::ExitCode;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use zeroize::Zeroize;
 
-use image::ImageFormat;
+#[cfg(feature = "image")]
+use crate::images::fit_image;
+use crate::process::Cmd;
+use crate::settings::{env_flag, env_u64, setting};
 
 const VERSION: &str = match option_env!("WSL_CLIP_BRIDGE_VERSION") {
     Some(v) => v,
@@ -21,6 +45,26 @@ const VERSION: &str = match option_env!("WSL_CLIP_BRIDGE_VERSION") {
 };
 
 const WL_TIMEOUT: Duration = Duration::from_secs(5);
+const WL_RETRY_BACKOFF: Duration = Duration::from_millis(100);
+const WSLPATH_TIMEOUT: Duration = Duration::from_secs(2);
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Per-call clipboard backend timeout (`WSL_CLIP_BRIDGE_WL_TIMEOUT_MS`).
+fn wl_timeout() -> Duration {
+    env_u64("WSL_CLIP_BRIDGE_WL_TIMEOUT_MS").map_or(WL_TIMEOUT, Duration::from_millis)
+}
+
+/// How often `--watch` polls the clipboard
+/// (`WSL_CLIP_BRIDGE_WATCH_INTERVAL_MS`).
+fn watch_interval() -> Duration {
+    env_u64("WSL_CLIP_BRIDGE_WATCH_INTERVAL_MS").map_or(WATCH_INTERVAL, Duration::from_millis)
+}
+
+/// Extra attempts after a transient backend failure
+/// (`WSL_CLIP_BRIDGE_WL_RETRIES`).
+fn wl_retries() -> u32 {
+    env_u64("WSL_CLIP_BRIDGE_WL_RETRIES").map_or(0, |n| u32::try_from(n).unwrap_or(u32::MAX))
+}
 
 fn print_help() {
     println!(
@@ -32,14 +76,115 @@ the BI_BITFIELDS variant that sharp/libvips rejects) and emits a PNG.
 
 USAGE:
     xclip -selection clipboard -t <MIME> -o
+    xclip -selection clipboard -t <MIME> [-i] < data
+    xclip -selection clipboard [-t auto] -i <FILE>...
 
 OPTIONS:
     -o              Output clipboard contents
+    -i              Copy stdin to the clipboard via wl-copy (the default
+                    when -o is not given)
     -t <MIME>       MIME type: TARGETS, text/plain, image/png, image/bmp, ...
-    -selection <S>  Ignored (xclip compat)
+                    or a comma list of fallbacks (image/webp,image/png).
+                    For -i, 'auto' sniffs the type from the data; omitted
+                    does too, except that piped text stays text/plain
+    -selection <S>  clipboard (default) or primary; any prefix works
+    --self-test     Check the environment, settings, clipboard backend,
+                    helper programs, a copy/paste round trip through the
+                    primary selection (which it clears), and BMP->PNG
+                    conversion; exit non-zero on any failure
+    --info [--json] Describe the selection's contents: each offered type
+                    with its size, image dimensions, and a content hash
+    --config        Print every WSL_CLIP_BRIDGE_* setting's effective value
+                    and whether it came from the environment, the profile,
+                    or the default
+    --raw           No conversion or cleanup: fetch exactly the requested
+                    type and emit its bytes untouched, or with -i, store
+                    the input as is
+    --watch         With -o, keep running and print the contents again each
+                    time they change, one record per line
+    -0, --null      With --watch, end records with NUL instead of newline
+    --sensitive     With -i, skip the copy hook and OSC 52, and clear the
+                    clipboard again after WSL_CLIP_BRIDGE_SENSITIVE_TTL_SECS
+                    unless it changed meanwhile
+    --ttl <secs>    With -i, clear the clipboard again after <secs> unless
+                    it changed meanwhile; overrides the --sensitive default
+    --no-expire     With -i --sensitive, skip the hook but never clear
+    --profile <P>   Image setting defaults: claude (fit API upload limits),
+                    lossless, or fast; variables that are set still win
     -h, --help      Show this help
     -V, --version   Show version
 
+ENVIRONMENT:
+    WSL_CLIP_BRIDGE_WSLPATH=1   Rewrite pasted Windows paths (C:\\...) to
+                                WSL paths (/mnt/c/...) in text/plain
+    WSL_CLIP_BRIDGE_WL_TIMEOUT_MS=<n>
+                                Backend call timeout (default 5000)
+    WSL_CLIP_BRIDGE_WL_RETRIES=<n>
+                                Retries after a timeout or failed compositor
+                                connection, with exponential backoff
+                                (default 0)
+    WSL_CLIP_BRIDGE_OSC52=auto|always|never
+                                Also send copied text to the terminal as
+                                OSC 52; auto does so over SSH or when
+                                wl-copy is unusable (default auto). Never
+                                for --sensitive copies
+    WSL_CLIP_BRIDGE_ON_COPY=<cmd>, WSL_CLIP_BRIDGE_ON_PASTE=<cmd>
+                                Shell command run after a copy / paste,
+                                with WSL_CLIP_BRIDGE_MIME and
+                                WSL_CLIP_BRIDGE_FILE (the payload) set
+    WSL_CLIP_BRIDGE_HOOK_TIMEOUT_MS=<n>
+                                Hook timeout (default 10000)
+    WSL_CLIP_BRIDGE_ALLOW_BINARY_MIME=<types>
+                                Comma-separated opaque types (e.g.
+                                application/pdf, application/*) to
+                                advertise and paste byte for byte
+    WSL_CLIP_BRIDGE_ALLOWED_MIME=<types>
+                                Only copy, paste and advertise these types
+    WSL_CLIP_BRIDGE_BLOCKED_MIME=<types>
+                                Never copy, paste or advertise these types
+                                (checked before ALLOWED_MIME)
+    WSL_CLIP_BRIDGE_TRANSCODE=0 Never transcode between image formats
+    WSL_CLIP_BRIDGE_BACKGROUND=#rrggbb
+                                Color transparent pixels become in JPEG
+                                output (default #ffffff)
+    WSL_CLIP_BRIDGE_JPEG_QUALITY=<1-100>
+                                JPEG quality when re-encoding (default 75)
+    WSL_CLIP_BRIDGE_PNG_COMPRESSION=fast|default|best|<0-9>
+                                PNG compression when re-encoding
+                                (default fast)
+    WSL_CLIP_BRIDGE_MAX_IMAGE_BYTES=<n>[,<type>=<n>...]
+                                Recompress and scale down pasted images
+                                until they fit in <n> bytes; per-type
+                                entries override, 0 means no limit
+    WSL_CLIP_BRIDGE_PROFILE=<P> Same as --profile
+    WSL_CLIP_BRIDGE_STRICT=1    Exit with an error instead of only warning
+                                about unknown or invalid settings
+    WSL_CLIP_BRIDGE_DECODE_LIMIT_MB=<n>
+                                Memory an image decode may use; larger
+                                images are refused (default 512)
+    WSL_CLIP_BRIDGE_OCR=1       Serve -t text/plain for image-only copies by
+                                running tesseract on the image (always
+                                done for -t 'text/plain;x-ocr')
+    WSL_CLIP_BRIDGE_OCR_LANG=<l>
+                                Tesseract languages, e.g. eng+deu
+    WSL_CLIP_BRIDGE_THUMBNAIL_SIZE=<px>
+                                Longest side of -t image/x-thumbnail
+                                (default 256)
+    WSL_CLIP_BRIDGE_ICC=keep|srgb|strip
+                                What happens to an image's color profile
+                                when it is re-encoded (default keep)
+    WSL_CLIP_BRIDGE_KEEP_INPUT_FORMAT=1
+                                Copy BMP and TIFF as they are instead of
+                                converting them to PNG
+    WSL_CLIP_BRIDGE_SENSITIVE_TTL_SECS=<n>
+                                Lifetime of a --sensitive copy (default 30)
+    WSL_CLIP_BRIDGE_WATCH_INTERVAL_MS=<n>
+                                --watch polling interval (default 500)
+    WSL_CLIP_BRIDGE_SVG_SIZE=<px>
+                                Longest side of PNGs rendered from an SVG
+                                (default: the SVG's own size; needs the
+                                svg build feature)
+
 Claude Code invokes:
     xclip -selection clipboard -t TARGETS -o
     xclip -selection clipboard -t image/png -o
@@ -50,16 +195,128 @@ Source: https://github.com/camjac251/wsl-clip-bridge"
     );
 }
 
+/// Which X11-style selection to read. Unlike real xclip the default is the
+/// clipboard, since that is what every caller of this shim means.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Selection {
+    Clipboard,
+    Primary,
+}
+
+impl Selection {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Clipboard => "clipboard",
+            Self::Primary => "primary",
+        }
+    }
+
+    /// Accepts any prefix of the name, as xclip does (`-selection c`).
+    fn parse(name: &str) -> Option<Self> {
+        if name.is_empty() {
+            None
+        } else if "clipboard".starts_with(name) {
+            Some(Self::Clipboard)
+        } else if "primary".starts_with(name) {
+            Some(Self::Primary)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Report {
+    Info(info::Format),
+    Config,
+}
+
+impl Report {
+    /// `--config` wins over `--info`; `--json` only changes `--info`.
+    fn from_flags(info: bool, json: bool, config: bool) -> Option<Self> {
+        if config {
+            return Some(Self::Config);
+        }
+        info.then_some(Self::Info(if json {
+            info::Format::Json
+        } else {
+            info::Format::Text
+        }))
+    }
+}
+
+/// `-i --ttl <secs>` / `--no-expire`: when the copy is cleared again,
+/// overriding the `--sensitive` default.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Expiry {
+    After(Duration),
+    Never,
+}
+
+impl Expiry {
+    /// The `--ttl` value; anything but a positive number of seconds is a
+    /// usage error, as is one too large to hand the worker in milliseconds.
+    fn parse(secs: &str) -> Self {
+        let Some(secs) = ttl_secs(secs) else {
+            eprintln!("xclip: --ttl needs a number of seconds, got {secs:?}");
+            std::process::exit(1);
+        };
+        Self::After(Duration::from_secs(secs))
+    }
+
+    /// The internal `--clear-after` value. Missing or garbled is an error,
+    /// never 0, which would clear the clipboard straight away.
+    fn worker_delay(millis: Option<String>) -> Duration {
+        let Some(millis) = millis.and_then(|ms| ms.parse().ok()) else {
+            eprintln!("xclip: --clear-after needs a number of milliseconds");
+            std::process::exit(1);
+        };
+        Duration::from_millis(millis)
+    }
+}
+
+/// A positive `--ttl`, small enough to hand the clear worker in milliseconds.
+fn ttl_secs(secs: &str) -> Option<u64> {
+    secs.parse::<u64>()
+        .ok()
+        .filter(|&s| s > 0 && s.checked_mul(1000).is_some())
+}
+
 struct Args {
     mime: Option<String>,
+    selection: Selection,
     output: bool,
+    raw: bool,
+    /// `--watch`, with its record terminator.
+    watch: Option<u8>,
+    sensitive: bool,
+    expiry: Option<Expiry>,
+    /// `--info` or `--config`: print a report instead of copying or pasting.
+    report: Option<Report>,
+    /// Internal: run as the `--sensitive` / `--ttl` expiry worker.
+    clear_after: Option<Duration>,
+    /// Files to copy instead of stdin, as in `xclip -i <file>`.
+    files: Vec<String>,
 }
 
 fn parse_args() -> Args {
     let mut args = Args {
         mime: None,
+        selection: Selection::Clipboard,
         output: false,
+        raw: false,
+        watch: None,
+        sensitive: false,
+        expiry: None,
+        report: None,
+        clear_after: None,
+        files: Vec::new(),
     };
+    let mut watch = false;
+    let mut delimiter = b'\n';
+    let mut info = false;
+    let mut json = false;
+    let mut config = false;
     let mut it = env::args().skip(1);
     while let Some(arg) = it.next() {
         match arg.as_str() {
@@ -71,149 +328,435 @@ fn parse_args() -> Args {
                 println!("wsl-clip-bridge {VERSION}");
                 std::process::exit(0);
             }
+            "--self-test" => {
+                std::process::exit(selftest::run());
+            }
             "-selection" => {
-                it.next(); // consume value, ignore
+                let name = it.next().unwrap_or_default();
+                let Some(sel) = Selection::parse(&name) else {
+                    eprintln!("xclip: unsupported selection: {name}");
+                    std::process::exit(1);
+                };
+                args.selection = sel;
             }
             "-t" => {
                 args.mime = it.next();
             }
-            "-o" => {
+            "-o" | "-out" => {
                 args.output = true;
             }
+            "-i" | "-in" => {
+                args.output = false;
+            }
+            "--raw" => {
+                args.raw = true;
+            }
+            "--watch" => {
+                watch = true;
+            }
+            "-0" | "--null" => {
+                delimiter = b'\0';
+            }
+            "--sensitive" => {
+                args.sensitive = true;
+            }
+            "--ttl" => {
+                args.expiry = Some(Expiry::parse(&it.next().unwrap_or_default()));
+            }
+            "--no-expire" => {
+                args.expiry = Some(Expiry::Never);
+            }
+            "--info" => {
+                info = true;
+            }
+            "--json" => {
+                json = true;
+            }
+            "--config" => {
+                config = true;
+            }
+            "--profile" => {
+                let name = it.next().unwrap_or_default();
+                if let Err(e) = profile::select(&name) {
+                    eprintln!("xclip: {e}");
+                    std::process::exit(1);
+                }
+            }
+            "--clear-after" => args.clear_after = Some(Expiry::worker_delay(it.next())),
+            // xclip options whose value we ignore; skip it so it is not
+            // taken for a file name.
+            "-d" | "-display" | "-l" | "-loops" => {
+                it.next();
+            }
+            file if !file.starts_with('-') => {
+                args.files.push(arg);
+            }
             _ => {}
         }
     }
+    args.watch = watch.then_some(delimiter);
+    args.report = Report::from_flags(info, json, config);
     args
 }
 
 fn main() -> ExitCode {
     let args = parse_args();
+    let problems = settings::problems();
+    for problem in &problems {
+        eprintln!("xclip: {problem}");
+    }
+    if !problems.is_empty() && env_flag("WSL_CLIP_BRIDGE_STRICT") {
+        return ExitCode::from(1);
+    }
+    let mime = args.mime.as_deref().unwrap_or("text/plain");
+    if let Some(delay) = args.clear_after {
+        let code = sensitive::clear_after(delay, mime, args.selection);
+        return ExitCode::from(u8::try_from(code).unwrap_or(1));
+    }
+    if let Some(report) = args.report {
+        let code = match report {
+            Report::Info(format) => info::run(args.selection, format),
+            Report::Config => settings::show(),
+        };
+        return ExitCode::from(u8::try_from(code).unwrap_or(1));
+    }
     if !args.output {
-        eprintln!(
-            "xclip: write mode (-i) is not implemented. This is a read-only Claude Code paste shim."
+        let code = copy(
+            args.mime.as_deref(),
+            &args.files,
+            args.selection,
+            args.sensitive,
+            args.raw,
+            args.expiry,
         );
-        return ExitCode::from(1);
+        return ExitCode::from(u8::try_from(code).unwrap_or(1));
     }
     let code = match args.mime.as_deref() {
-        Some("TARGETS") => print_targets(),
-        Some(m) => output(m),
-        None => output("text/plain"),
+        Some("TARGETS") => print_targets(args.selection, args.raw),
+        spec => {
+            let spec = spec.unwrap_or("text/plain");
+            if let Some(delimiter) = args.watch {
+                watch(spec, args.selection, args.raw, delimiter)
+            } else {
+                output(spec, args.selection, args.raw)
+            }
+        }
     };
     ExitCode::from(u8::try_from(code).unwrap_or(1))
 }
 
 // ---------------------------------------------------------------------------
-// wl-paste runner
+// wl-paste / wl-copy runner
 // ---------------------------------------------------------------------------
 
+fn wayland_ready() -> io::Result<()> {
+    wsl::wayland_unavailable().map_or(Ok(()), |reason| {
+        Err(io::Error::new(io::ErrorKind::NotFound, reason))
+    })
+}
+
 fn run_wl_paste(extra_args: &[&str]) -> io::Result<Vec<u8>> {
-    let mut cmd = Command::new("wl-paste");
-    cmd.args(extra_args)
-        .stdout(std::process::Stdio::piped())
-        .stderr(std::process::Stdio::piped());
-
-    let mut child = cmd.spawn()?;
-    let child_stdout = child.stdout.take();
-    let child_stderr = child.stderr.take();
-
-    // Drain both pipes from dedicated threads so neither can deadlock the
-    // child by filling its kernel pipe buffer.
-    let (stdout_tx, stdout_rx) = mpsc::channel();
-    let (stderr_tx, stderr_rx) = mpsc::channel();
-    let stdout_reader = thread::spawn(move || {
-        let mut buf = Vec::new();
-        if let Some(mut out) = child_stdout {
-            let _ = out.read_to_end(&mut buf);
-        }
-        let _ = stdout_tx.send(buf);
-    });
-    let stderr_reader = thread::spawn(move || {
-        let mut buf = Vec::new();
-        if let Some(mut err) = child_stderr {
-            let _ = err.read_to_end(&mut buf);
-        }
-        let _ = stderr_tx.send(buf);
-    });
+    wayland_ready()?;
+    with_retries(|| wl_cmd("wl-paste").args(extra_args).run())
+}
+
+/// A wl-clipboard command pointed at the socket found by [`wsl::environment`],
+/// so it connects even when `XDG_RUNTIME_DIR` lacks the socket and only
+/// `WSLg`'s runtime directory has it.
+fn wl_cmd(program: &str) -> Cmd<'_> {
+    let cmd = Cmd::new(program, wl_timeout());
+    match &wsl::environment().wayland_socket {
+        Some(socket) => cmd.env("WAYLAND_DISPLAY", socket.as_os_str()),
+        None => cmd,
+    }
+}
 
-    let start = Instant::now();
+/// Retry `call` on failures that a flaky `WSLg` (typically right after
+/// resume) produces: timeouts and failed compositor connections. Anything
+/// else, notably "type not offered", fails straight away so format probing
+/// stays fast.
+fn with_retries<T>(mut call: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    let mut backoff = WL_RETRY_BACKOFF;
+    let mut attempts_left = wl_retries();
     loop {
-        if let Some(status) = child.try_wait()? {
-            let _ = stdout_reader.join();
-            let _ = stderr_reader.join();
-            let stdout = stdout_rx.recv().unwrap_or_default();
-            let stderr = stderr_rx.recv().unwrap_or_default();
-            if status.success() {
-                return Ok(stdout);
-            }
-            let stderr_text = String::from_utf8_lossy(&stderr);
-            let trimmed = stderr_text.trim();
-            return Err(io::Error::other(if trimmed.is_empty() {
-                format!("wl-paste exited with {status}")
-            } else {
-                format!("wl-paste exited with {status}: {trimmed}")
-            }));
-        }
-        if start.elapsed() > WL_TIMEOUT {
-            let _ = child.kill();
-            let _ = child.wait();
-            let _ = stdout_reader.join();
-            let _ = stderr_reader.join();
-            return Err(io::Error::new(
-                io::ErrorKind::TimedOut,
-                "wl-paste timed out after 5s",
-            ));
+        match call() {
+            Err(e) if attempts_left > 0 && is_transient(&e) => {
+                attempts_left -= 1;
+                std::thread::sleep(backoff);
+                backoff *= 2;
+            }
+            result => return result,
         }
-        thread::sleep(Duration::from_millis(50));
     }
 }
 
-fn wl_list_types() -> io::Result<Vec<String>> {
-    let bytes = run_wl_paste(&["--list-types"])?;
+fn is_transient(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::TimedOut || e.to_string().contains("Failed to connect")
+}
+
+/// Hand `data` to `wl-copy`, which forks a server that keeps offering it to
+/// the compositor after we exit.
+fn wl_copy(data: &[u8], mime: &str, sel: Selection) -> io::Result<()> {
+    wayland_ready()?;
+    let mut args = vec!["--type", mime];
+    if sel == Selection::Primary {
+        args.push("--primary");
+    }
+    with_retries(|| {
+        wl_cmd("wl-copy")
+            .args(&args)
+            .stdin(data)
+            .discard_output()
+            .run()
+    })
+    .map(drop)
+}
+
+fn wl_list_types(sel: Selection) -> io::Result<Vec<String>> {
+    let bytes = match sel {
+        Selection::Clipboard => run_wl_paste(&["--list-types"])?,
+        Selection::Primary => run_wl_paste(&["--primary", "--list-types"])?,
+    };
     Ok(String::from_utf8_lossy(&bytes)
         .lines()
         .map(str::to_owned)
         .collect())
 }
 
-fn wl_fetch(mime: &str) -> io::Result<Vec<u8>> {
-    run_wl_paste(&["-t", mime])
+fn wl_fetch(mime: &str, sel: Selection) -> io::Result<Vec<u8>> {
+    match sel {
+        Selection::Clipboard => run_wl_paste(&["-t", mime]),
+        Selection::Primary => run_wl_paste(&["--primary", "-t", mime]),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Backend dispatch
+// ---------------------------------------------------------------------------
+
+// wl-paste is the backend everywhere WSLg or another compositor is present.
+// The X11 backend only takes over when there is an X server but no Wayland.
+
+static SEEN_TYPES: Mutex<Vec<(Selection, Vec<String>)>> = Mutex::new(Vec::new());
+
+/// Drop the memoized type lists, for long-running callers like `--watch`
+/// that must notice a new copy.
+fn forget_types() {
+    SEEN_TYPES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clear();
+}
+
+/// The offered types, asked of the backend once per selection per process.
+/// Fallback lists and parameter-insensitive matching would otherwise
+/// re-run `wl-paste --list-types` for every candidate.
+fn list_types(sel: Selection) -> io::Result<Vec<String>> {
+    let cached = SEEN_TYPES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .find(|(s, _)| *s == sel)
+        .map(|(_, types)| types.clone());
+    if let Some(types) = cached {
+        return Ok(types);
+    }
+    let mut types = backend_list_types(sel)?;
+    types.retain(|t| mime_permitted(t));
+    SEEN_TYPES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push((sel, types.clone()));
+    Ok(types)
+}
+
+fn backend_list_types(sel: Selection) -> io::Result<Vec<String>> {
+    #[cfg(feature = "x11")]
+    if wsl::prefer_x11() {
+        return x11::list_types(sel, wl_timeout());
+    }
+    wl_list_types(sel)
+}
+
+fn fetch(mime: &str, sel: Selection) -> io::Result<Vec<u8>> {
+    #[cfg(feature = "x11")]
+    if wsl::prefer_x11() {
+        return x11::fetch(mime, sel, wl_timeout());
+    }
+    wl_fetch(mime, sel)
+}
+
+/// Like `fetch`, but when the exact spelling is not offered, retry with
+/// the offered type that shares its base (`text/plain` vs
+/// `text/plain;charset=utf-8`), keeping the source's own parameters.
+fn fetch_matching(mime: &str, sel: Selection) -> io::Result<Vec<u8>> {
+    fetch(mime, sel).or_else(|err| {
+        let offered = list_types(sel).ok().and_then(|types| {
+            types
+                .into_iter()
+                .find(|t| t != mime && same_base_mime(t, mime))
+        });
+        offered.map_or(Err(err), |t| fetch(&t, sel))
+    })
+}
+
+/// The `type/subtype` part of a MIME string, without parameters.
+fn base_mime(mime: &str) -> &str {
+    mime.split(';').next().unwrap_or(mime).trim()
+}
+
+fn same_base_mime(a: &str, b: &str) -> bool {
+    base_mime(a).eq_ignore_ascii_case(base_mime(b))
+}
+
+/// Text formats registered outside `text/*`.
+const TEXT_APPLICATION_TYPES: [&str; 8] = [
+    "application/json",
+    "application/xml",
+    "application/yaml",
+    "application/x-yaml",
+    "application/toml",
+    "application/javascript",
+    "application/sql",
+    "application/x-sh",
+];
+
+/// Whether `mime` is textual: any `text/*`, the structured formats above,
+/// and `+json`/`+xml` suffixed application types (`application/ld+json`,
+/// but not `image/svg+xml`).
+fn is_text(mime: &str) -> bool {
+    let base = base_mime(mime).to_ascii_lowercase();
+    base.starts_with("text/")
+        || TEXT_APPLICATION_TYPES.contains(&base.as_str())
+        || (base.starts_with("application/") && (base.ends_with("+json") || base.ends_with("+xml")))
+}
+
+/// Whether `mime` is one of the opaque types listed in
+/// `WSL_CLIP_BRIDGE_ALLOW_BINARY_MIME` (comma-separated; `type/*` matches a
+/// whole family), which are passed through byte for byte.
+fn binary_allowed(mime: &str) -> bool {
+    setting("WSL_CLIP_BRIDGE_ALLOW_BINARY_MIME").is_some_and(|list| listed(&list, mime))
+}
+
+/// The MIME policy: `WSL_CLIP_BRIDGE_BLOCKED_MIME` types are never copied,
+/// pasted or advertised, and when `WSL_CLIP_BRIDGE_ALLOWED_MIME` is set,
+/// nothing outside it is either.
+fn mime_permitted(mime: &str) -> bool {
+    policy_permits(
+        setting("WSL_CLIP_BRIDGE_ALLOWED_MIME").as_deref(),
+        setting("WSL_CLIP_BRIDGE_BLOCKED_MIME").as_deref(),
+        mime,
+    )
+}
+
+fn policy_permits(allowed: Option<&str>, blocked: Option<&str>, mime: &str) -> bool {
+    if blocked.is_some_and(|list| listed(list, mime)) {
+        return false;
+    }
+    allowed
+        .filter(|list| !list.trim().is_empty())
+        .is_none_or(|list| listed(list, mime))
+}
+
+/// Whether `mime` matches an entry of a comma-separated pattern list.
+fn listed(list: &str, mime: &str) -> bool {
+    list.split(',')
+        .map(str::trim)
+        .filter(|m| !m.is_empty())
+        .any(|pattern| mime_matches(pattern, mime))
+}
+
+fn mime_matches(pattern: &str, mime: &str) -> bool {
+    pattern.strip_suffix("/*").map_or_else(
+        || same_base_mime(pattern, mime),
+        |family| {
+            base_mime(mime)
+                .split_once('/')
+                .is_some_and(|(t, _)| t.eq_ignore_ascii_case(family))
+        },
+    )
 }
 
 // ---------------------------------------------------------------------------
 // xclip verbs
 // ---------------------------------------------------------------------------
 
-fn print_targets() -> i32 {
-    let types = match wl_list_types() {
+fn print_targets(sel: Selection, raw: bool) -> i32 {
+    let types = match list_types(sel) {
         Ok(t) => t,
         Err(e) => {
-            eprintln!("xclip: wl-paste --list-types failed: {e}");
+            eprintln!("xclip: listing clipboard types failed: {e}");
             return 1;
         }
     };
-    let has_bmp = types.iter().any(|t| t == "image/bmp");
-    let has_png = types.iter().any(|t| t == "image/png");
+    if raw {
+        for t in &types {
+            println!("{t}");
+        }
+        return i32::from(types.is_empty());
+    }
+    let has_bmp = cfg!(feature = "image")
+        && types.iter().any(|t| {
+            same_base_mime(t, "image/bmp")
+                || (cfg!(feature = "tiff") && same_base_mime(t, "image/tiff"))
+        });
+    let has_png = types.iter().any(|t| same_base_mime(t, "image/png"));
+    let has_svg = cfg!(feature = "svg") && types.iter().any(|t| same_base_mime(t, "image/svg+xml"));
     let mut count: usize = 0;
 
     // The one real contribution: advertise image/png when the clipboard only
     // has a BMP, so Claude Code's paste path tries the PNG MIME first and we
     // can hand back a converted PNG from output().
-    if has_bmp && !has_png {
+    if (has_bmp || has_svg) && !has_png && mime_permitted("image/png") {
         println!("image/png");
         count += 1;
     }
+    // Likewise text/plain for file-list and HTML-only copies, rendered by
+    // plain_text(), and a uri-list for Nautilus's own file-list type.
+    let offered = |m| types.iter().any(|t| same_base_mime(t, m));
+    let has_files = offered("text/uri-list") || offered(GNOME_COPIED_FILES);
+    if !offered("text/plain") && (has_files || offered("text/html")) && mime_permitted("text/plain")
+    {
+        println!("text/plain");
+        count += 1;
+    }
+    if offered(GNOME_COPIED_FILES) && !offered("text/uri-list") && mime_permitted("text/uri-list") {
+        println!("text/uri-list");
+        count += 1;
+    }
 
+    // Advertise each type exactly as offered, parameters included, so
+    // consumers that match on the full string see the flavor they expect.
     for t in &types {
-        match t.as_str() {
-            "image/png" | "image/jpeg" | "image/gif" | "image/webp" | "image/bmp" => {
+        match base_mime(t) {
+            "image/png" | "image/jpeg" | "image/gif" | "image/webp" | "image/avif"
+            | "image/bmp" | "image/tiff" | "image/svg+xml" => {
                 println!("{t}");
                 count += 1;
-                if t == "image/jpeg" {
+                if cfg!(feature = "image")
+                    && base_mime(t) == "image/jpeg"
+                    && mime_permitted("image/jpg")
+                {
                     println!("image/jpg");
                     count += 1;
                 }
             }
-            s if s.starts_with("text/") => {
+            // Word and Outlook offer RTF under either name; advertise the
+            // other spelling too, fetch_as() maps between them.
+            "text/rtf" | "application/rtf" => {
+                println!("{t}");
+                count += 1;
+                let alias = rtf_alias(t);
+                if !types.iter().any(|o| same_base_mime(o, alias)) && mime_permitted(alias) {
+                    println!("{alias}");
+                    count += 1;
+                }
+            }
+            _ if is_text(t)
+                || binary_allowed(t)
+                || same_base_mime(t, GNOME_COPIED_FILES)
+                || same_base_mime(t, KDE_CUT_SELECTION) =>
+            {
                 println!("{t}");
                 count += 1;
             }
@@ -224,52 +767,576 @@ fn print_targets() -> i32 {
     i32::from(count == 0)
 }
 
-fn output(mime: &str) -> i32 {
-    match mime {
-        m if m.starts_with("text/") => passthrough(m),
-        // Try PNG directly first. On WSLg the clipboard only advertises BMP,
-        // so this call fails fast and we fall through to the BMP decoder.
-        "image/png" => wl_fetch("image/png").map_or_else(|_| bmp_to_png(), |d| write_stdout(&d)),
-        "image/jpg" => passthrough("image/jpeg"),
-        "image/jpeg" | "image/gif" | "image/webp" | "image/bmp" => passthrough(mime),
-        _ => {
-            eprintln!("xclip: unsupported MIME type: {mime}");
+/// Serve `-o`. `spec` is a single MIME type or a comma-separated list of
+/// fallbacks (`image/webp,image/png`); the first one we can produce wins and,
+/// for lists, is reported on stderr so callers know what they got.
+fn output(spec: &str, sel: Selection, raw: bool) -> i32 {
+    if raw
+        && !spec.contains(',')
+        && let Some(code) = stream(spec, sel)
+    {
+        return code;
+    }
+    match produce(spec, sel, raw) {
+        Ok((mime, mut data)) => {
+            if spec.contains(',') {
+                eprintln!("xclip: selected {mime}");
+            }
+            let code = write_stdout(&data);
+            let secret = sensitive::is_marked(sel);
+            if code == 0 && !secret {
+                hooks::run(hooks::Event::Paste, &mime, sel, &data);
+            }
+            if secret {
+                data.zeroize();
+            }
+            code
+        }
+        Err(errors) => {
+            for e in errors {
+                eprintln!("xclip: {e}");
+            }
             1
         }
     }
 }
 
-fn passthrough(mime: &str) -> i32 {
-    match wl_fetch(mime) {
-        Ok(d) => write_stdout(&d),
+/// Serve `-raw -o` by letting `wl-paste` write to our stdout, so a large
+/// payload is never held in memory here. `None` when the buffered path has
+/// to run instead: the X11 backend, a paste hook that needs the data, or a
+/// type that is not offered (whose error the buffered path reports).
+fn stream(mime: &str, sel: Selection) -> Option<i32> {
+    #[cfg(feature = "x11")]
+    if wsl::prefer_x11() {
+        return None;
+    }
+    if hooks::configured(hooks::Event::Paste) || !mime_permitted(mime) {
+        return None;
+    }
+    let types = list_types(sel).ok()?;
+    let offered = types
+        .iter()
+        .find(|t| *t == mime)
+        .or_else(|| types.iter().find(|t| same_base_mime(t, mime)))?;
+    let mut args = vec!["-t", offered.as_str()];
+    if sel == Selection::Primary {
+        args.push("--primary");
+    }
+    // No timeout: the reader sets the pace (`| less` can sit for minutes),
+    // and listing the types just showed the compositor answering.
+    let result = wl_cmd("wl-paste")
+        .args(&args)
+        .timeout(Duration::MAX)
+        .inherit_stdout()
+        .run();
+    Some(match result {
+        Ok(_) => 0,
         Err(e) => {
-            eprintln!("xclip: wl-paste -t {mime} failed: {e}");
+            eprintln!("xclip: reading {mime} failed: {e}");
             1
         }
+    })
+}
+
+/// Serve `-o --watch`: print the contents, then poll and print them again
+/// whenever they change, each record followed by `delimiter`. `WSLg` has no
+/// clipboard-change notification a client can subscribe to, so this polls,
+/// but from one process instead of a shell loop. Runs until stdout closes.
+fn watch(spec: &str, sel: Selection, raw: bool, delimiter: u8) -> i32 {
+    let interval = watch_interval();
+    let mut last: Option<(String, Vec<u8>)> = None;
+    loop {
+        forget_types();
+        match produce(spec, sel, raw) {
+            Ok(current) if last.as_ref() != Some(&current) => {
+                let (mime, data) = &current;
+                if spec.contains(',') && last.as_ref().is_none_or(|(m, _)| m != mime) {
+                    eprintln!("xclip: selected {mime}");
+                }
+                let mut record = data.clone();
+                if delimiter != b'\n' || record.last() != Some(&b'\n') {
+                    record.push(delimiter);
+                }
+                let mut stdout = io::stdout().lock();
+                if stdout
+                    .write_all(&record)
+                    .and_then(|()| stdout.flush())
+                    .is_err()
+                {
+                    return 0;
+                }
+                if !sensitive::is_marked(sel) {
+                    hooks::run(hooks::Event::Paste, mime, sel, data);
+                }
+                last = Some(current);
+            }
+            Ok(_) => {}
+            // Empty clipboard or nothing convertible: wait for the next copy,
+            // and print it even if it repeats what was there before.
+            Err(_) => last = None,
+        }
+        std::thread::sleep(interval);
     }
 }
 
-fn bmp_to_png() -> i32 {
-    let bmp = match wl_fetch("image/bmp") {
-        Ok(d) => d,
-        Err(e) => {
-            eprintln!("xclip: wl-paste -t image/bmp failed: {e}");
-            return 1;
+/// Produce the first type in `spec` that the clipboard can satisfy, as
+/// `(mime, bytes)`, or every candidate's error.
+fn produce(spec: &str, sel: Selection, raw: bool) -> Result<(String, Vec<u8>), Vec<String>> {
+    let mut errors = Vec::new();
+    for mime in spec.split(',').map(str::trim).filter(|m| !m.is_empty()) {
+        if !mime_permitted(mime) {
+            errors.push(format!("{mime} is not permitted by the MIME policy"));
+            continue;
         }
-    };
-    let img = match image::load_from_memory(&bmp) {
-        Ok(i) => i,
-        Err(e) => {
-            eprintln!("xclip: BMP decode failed: {e}");
-            return 1;
+        let fetched = if raw {
+            passthrough(mime, sel)
+        } else {
+            fetch_as(mime, sel).map(|data| fit_image(data, mime))
+        };
+        match fetched {
+            Ok(data) => return Ok((mime.to_owned(), data)),
+            Err(e) => errors.push(e),
+        }
+    }
+    if errors.is_empty() {
+        errors.push(format!("unsupported MIME type: {spec}"));
+    }
+    Err(errors)
+}
+
+/// Built without the `image` feature: images are never re-encoded.
+#[cfg(not(feature = "image"))]
+const fn fit_image(data: Vec<u8>, _mime: &str) -> Vec<u8> {
+    data
+}
+
+fn fetch_as(mime: &str, sel: Selection) -> Result<Vec<u8>, String> {
+    let secret = sensitive::is_marked(sel);
+    // Asked for an encoding the clipboard does not hold: convert our UTF-8.
+    if let Some(charset) = text::Charset::of(mime)
+        && charset != text::Charset::Utf8
+        && !offers_charset(mime, charset, sel)
+    {
+        return fetch_as(base_mime(mime), sel).map(|utf8| {
+            converted(utf8, secret, |utf8| {
+                text::from_utf8(&String::from_utf8_lossy(utf8), charset)
+            })
+        });
+    }
+    match base_mime(mime) {
+        "text/html" => passthrough(mime, sel)
+            .map(|html| converted(html, secret, |html| text::strip_cf_html(html).to_vec())),
+        "text/plain" if ocr::requested(mime) => ocr_text(sel),
+        // wslpath takes the paths as arguments, which `ps` shows.
+        "text/plain" if env_flag("WSL_CLIP_BRIDGE_WSLPATH") && !secret => plain_text(mime, sel)
+            .map(|data| utf16_to_utf8(mime, data, false))
+            .map(to_wsl_paths),
+        "text/plain" => plain_text(mime, sel).map(|data| utf16_to_utf8(mime, data, secret)),
+        "text/rtf" | "application/rtf" => {
+            passthrough(mime, sel).or_else(|err| passthrough(rtf_alias(mime), sel).map_err(|_| err))
         }
+        "text/uri-list" => uri_list(sel),
+        _ if is_text(mime) => passthrough(mime, sel).map(|data| utf16_to_utf8(mime, data, secret)),
+        // Try PNG directly first. On WSLg the clipboard only advertises BMP,
+        // so this call fails fast and we fall through to the BMP decoder.
+        #[cfg(feature = "image")]
+        "image/png" => images::clipboard_png(sel),
+        #[cfg(feature = "image")]
+        "image/jpg" => images::image_as("image/jpeg", sel),
+        #[cfg(feature = "image")]
+        "image/x-thumbnail" => images::thumbnail(sel),
+        #[cfg(feature = "image")]
+        "image/jpeg" | "image/gif" | "image/webp" | "image/avif" | "image/bmp" | "image/tiff" => {
+            images::image_as(mime, sel)
+        }
+        // Built without the `image` feature: images are only passed through.
+        #[cfg(not(feature = "image"))]
+        "image/png" | "image/jpeg" | "image/gif" | "image/webp" | "image/avif" | "image/bmp"
+        | "image/tiff" => passthrough(mime, sel),
+        "image/svg+xml" | GNOME_COPIED_FILES | KDE_CUT_SELECTION => passthrough(mime, sel),
+        _ if binary_allowed(mime) => passthrough(mime, sel),
+        _ => Err(format!("unsupported MIME type: {mime}")),
+    }
+}
+
+fn offers_charset(mime: &str, charset: text::Charset, sel: Selection) -> bool {
+    list_types(sel).is_ok_and(|types| {
+        types
+            .iter()
+            .any(|t| same_base_mime(t, mime) && text::Charset::of(t) == Some(charset))
+    })
+}
+
+/// Windows sources can hand over UTF-16 with a byte-order mark under a
+/// plain text type; callers expect UTF-8, unless `mime` asked for another
+/// charset the clipboard offers as is.
+fn utf16_to_utf8(mime: &str, data: Vec<u8>, secret: bool) -> Vec<u8> {
+    let wants_utf8 = text::Charset::of(mime).is_none_or(|c| c == text::Charset::Utf8);
+    if wants_utf8 && text::has_utf16_bom(&data) {
+        converted(data, secret, |utf16| {
+            text::to_utf8(utf16, text::Charset::Utf16Le).into_bytes()
+        })
+    } else {
+        data
+    }
+}
+
+/// `convert` the fetched `data`, wiping the original afterwards when it
+/// is a secret, so only the converted copy is left in memory.
+fn converted(mut data: Vec<u8>, secret: bool, convert: impl FnOnce(&[u8]) -> Vec<u8>) -> Vec<u8> {
+    let out = convert(&data);
+    if secret {
+        data.zeroize();
+    }
+    out
+}
+
+/// The other registered name for RTF.
+fn rtf_alias(mime: &str) -> &'static str {
+    if same_base_mime(mime, "text/rtf") {
+        "application/rtf"
+    } else {
+        "text/rtf"
+    }
+}
+
+/// Nautilus and other GNOME apps' file list: `copy` or `cut`, then URIs.
+const GNOME_COPIED_FILES: &str = "x-special/gnome-copied-files";
+/// Dolphin's flag (`1`) that the accompanying uri-list was cut, not copied.
+const KDE_CUT_SELECTION: &str = "application/x-kde-cutselection";
+
+/// The copied files as a `text/uri-list`, from the clipboard's own or from
+/// a GNOME file list.
+fn uri_list(sel: Selection) -> Result<Vec<u8>, String> {
+    passthrough("text/uri-list", sel).or_else(|err| {
+        passthrough(GNOME_COPIED_FILES, sel)
+            .ok()
+            .and_then(|files| text::gnome_files_to_uri_list(&String::from_utf8_lossy(&files)))
+            .map(String::into_bytes)
+            .ok_or(err)
+    })
+}
+
+/// `text/plain`, or a plain rendering of the richer type that was copied
+/// instead: the paths of a file-manager copy (`text/uri-list` or GNOME's
+/// file list), the text of an HTML-only browser or Office selection, or,
+/// if enabled, the OCR'd text of an image-only copy.
+fn plain_text(mime: &str, sel: Selection) -> Result<Vec<u8>, String> {
+    passthrough(mime, sel).or_else(|err| {
+        let types = list_types(sel).unwrap_or_default();
+        let offered = |m| types.iter().any(|t| same_base_mime(t, m));
+        if offered("text/uri-list") || offered(GNOME_COPIED_FILES) {
+            let list = uri_list(sel)?;
+            Ok(text::uri_list_to_paths(&String::from_utf8_lossy(&list)).into_bytes())
+        } else if offered("text/html") {
+            let html = passthrough("text/html", sel)?;
+            let html = String::from_utf8_lossy(text::strip_cf_html(&html));
+            Ok(text::html_to_text(&html).into_bytes())
+        } else if ocr::fallback_enabled()
+            && types.iter().any(|t| base_mime(t).starts_with("image/"))
+        {
+            ocr_text(sel)
+        } else {
+            Err(err)
+        }
+    })
+}
+
+/// The text in the clipboard image, whatever format it was copied as.
+#[cfg(feature = "image")]
+fn ocr_text(sel: Selection) -> Result<Vec<u8>, String> {
+    images::clipboard_png(sel).and_then(|png| ocr::recognize(&png))
+}
+
+#[cfg(not(feature = "image"))]
+fn ocr_text(_sel: Selection) -> Result<Vec<u8>, String> {
+    Err("OCR needs a build with the image feature".to_owned())
+}
+
+fn passthrough(mime: &str, sel: Selection) -> Result<Vec<u8>, String> {
+    fetch_matching(mime, sel).map_err(|e| format!("reading {mime} failed: {e}"))
+}
+
+/// Turn pasted Windows paths (Explorer's "Copy as path") into their WSL
+/// form via `wslpath`, which knows the distro's automount root.
+fn to_wsl_paths(data: Vec<u8>) -> Vec<u8> {
+    let text = match String::from_utf8(data) {
+        Ok(t) => t,
+        Err(e) => return e.into_bytes(),
+    };
+    text::rewrite_windows_paths(&text, |path| {
+        let out = Cmd::new("wslpath", WSLPATH_TIMEOUT)
+            .args(&["-u", path])
+            .run()
+            .ok()?;
+        let linux = String::from_utf8(out).ok()?;
+        Some(linux.trim_end_matches('\n').to_owned())
+    })
+    .into_bytes()
+}
+
+fn parse_hex_color(s: &str) -> Option<[u8; 3]> {
+    let hex = s.trim().trim_start_matches('#');
+    let hex = match hex.len() {
+        3 => hex.chars().flat_map(|c| [c, c]).collect(),
+        6 => hex.to_owned(),
+        _ => return None,
     };
-    let mut buf = Cursor::new(Vec::new());
-    if let Err(e) = img.write_to(&mut buf, ImageFormat::Png) {
-        eprintln!("xclip: PNG encode failed: {e}");
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// Serve `-i`: read `files` (or stdin) and publish the data as `mime` via
+/// `wl-copy`, and for text also as OSC 52 when [`osc52::wanted`] says so.
+/// Without `-t`, or with `-t auto`, the type is sniffed from the data. A
+/// `sensitive` copy skips the copy hook and OSC 52 and is cleared again
+/// after [`sensitive::ttl`], or when `expiry` says.
+fn copy(
+    mime: Option<&str>,
+    files: &[String],
+    sel: Selection,
+    sensitive: bool,
+    raw: bool,
+    expiry: Option<Expiry>,
+) -> i32 {
+    let mut data = Vec::new();
+    if sensitive {
+        // Room for any password up front, so growing the buffer never
+        // leaves a partial copy behind in freed memory.
+        data.reserve(64 * 1024);
+    }
+    if files.is_empty()
+        && let Err(e) = io::stdin().read_to_end(&mut data)
+    {
+        eprintln!("xclip: reading stdin failed: {e}");
+        return 1;
+    }
+    for file in files {
+        match std::fs::read(file) {
+            Ok(bytes) if data.is_empty() => data = bytes,
+            Ok(mut bytes) => {
+                data.extend_from_slice(&bytes);
+                if sensitive {
+                    bytes.zeroize();
+                }
+            }
+            Err(e) => {
+                eprintln!("xclip: {file}: {e}");
+                return 1;
+            }
+        }
+    }
+    let name = files.first().map(String::as_str);
+    let code = publish(&mut data, mime, name, sel, sensitive, raw, expiry);
+    if sensitive {
+        data.zeroize();
+    }
+    code
+}
+
+/// The rest of [`copy`], once the input is read. Buffers the data is
+/// converted out of are wiped when it is `sensitive`.
+fn publish(
+    data: &mut Vec<u8>,
+    mime: Option<&str>,
+    name: Option<&str>,
+    sel: Selection,
+    sensitive: bool,
+    raw: bool,
+    expiry: Option<Expiry>,
+) -> i32 {
+    let mime = input_mime(mime, data, name);
+    let mime = &copy_form(data, mime, sensitive, raw);
+    if !mime_permitted(mime) {
+        eprintln!("xclip: not copying {mime}: not permitted by the MIME policy");
         return 1;
     }
-    write_stdout(&buf.into_inner())
+    let backend = copy_backend();
+    let mut sent_osc52 = false;
+    // Nothing could clear a secret from the terminal's clipboard again.
+    if !sensitive && is_text(mime) && osc52::wanted(backend.is_ok()) {
+        match osc52::send(data, sel) {
+            Ok(()) => sent_osc52 = true,
+            Err(e) => eprintln!("xclip: OSC 52 to the terminal failed: {e}"),
+        }
+    }
+    let copied = match backend {
+        Err(_) if sent_osc52 => true,
+        Err(reason) => {
+            eprintln!("xclip: {reason}");
+            false
+        }
+        Ok(()) => match wl_copy(data, mime, sel) {
+            Ok(()) => {
+                let clear_after = match expiry {
+                    Some(Expiry::After(delay)) => Some(delay),
+                    Some(Expiry::Never) => None,
+                    None => sensitive.then(sensitive::ttl),
+                };
+                if let Some(delay) = clear_after
+                    && let Err(e) = sensitive::schedule_clear(data, mime, sel, delay)
+                {
+                    eprintln!("xclip: scheduling the clipboard clear failed: {e}");
+                }
+                true
+            }
+            Err(e) => {
+                eprintln!("xclip: wl-copy failed: {e}");
+                false
+            }
+        },
+    };
+    if copied && !sensitive {
+        hooks::run(hooks::Event::Copy, mime, sel, data);
+    }
+    i32::from(!copied)
+}
+
+/// Convert `data` to the form Wayland apps read best, text as UTF-8 and
+/// BMP or TIFF as PNG, unless `raw`, and return its MIME type. Buffers
+/// converted out of are wiped when the copy is `sensitive`.
+fn copy_form(data: &mut Vec<u8>, mime: &str, sensitive: bool, raw: bool) -> String {
+    if raw {
+        return mime.to_owned();
+    }
+    let replace = |data: &mut Vec<u8>, new: Vec<u8>| {
+        if sensitive {
+            data.zeroize();
+        }
+        *data = new;
+    };
+    let mut mime = mime.to_owned();
+    // Store text as UTF-8, which is what Wayland apps read.
+    let charset = text::Charset::of(&mime);
+    if is_text(&mime)
+        && (charset.is_some_and(|c| c != text::Charset::Utf8) || text::has_utf16_bom(data))
+    {
+        let utf8 = text::to_utf8(data, charset.unwrap_or(text::Charset::Utf8)).into_bytes();
+        replace(data, utf8);
+        mime = format!("{};charset=utf-8", base_mime(&mime));
+    }
+    // Wayland apps take PNG far more often than BMP or TIFF.
+    #[cfg(feature = "image")]
+    if matches!(base_mime(&mime), "image/bmp" | "image/tiff")
+        && !env_flag("WSL_CLIP_BRIDGE_KEEP_INPUT_FORMAT")
+    {
+        match images::encode_png(data) {
+            Ok(png) => {
+                replace(data, png);
+                "image/png".clone_into(&mut mime);
+            }
+            Err(e) => eprintln!("xclip: copying {mime} as is: {e}"),
+        }
+    }
+    mime
+}
+
+/// The type to copy `data` as: `-t` if given, except `auto`. Like xclip,
+/// text piped in without `-t` is `text/plain` whatever it starts with;
+/// anything else is sniffed.
+fn input_mime<'a>(mime: Option<&'a str>, data: &[u8], name: Option<&str>) -> &'a str {
+    match mime {
+        None if name.is_none()
+            && (std::str::from_utf8(data).is_ok() || text::has_utf16_bom(data)) =>
+        {
+            "text/plain"
+        }
+        None | Some("auto") => sniff_mime(data, name),
+        Some(m) => m,
+    }
+}
+
+/// Guess the MIME type of data being copied: image formats from their magic
+/// bytes, then a few text formats by `name`'s extension, then `text/plain`
+/// for anything that is UTF-8 (xclip's default) or UTF-16 with a byte-order
+/// mark, and `application/octet-stream` for the rest.
+fn sniff_mime(data: &[u8], name: Option<&str>) -> &'static str {
+    if let Some(mime) = image_magic(data) {
+        return mime;
+    }
+    if data.starts_with(b"%PDF-") {
+        return "application/pdf";
+    }
+    if text::has_utf16_bom(data) {
+        return "text/plain";
+    }
+    let Ok(text) = std::str::from_utf8(data) else {
+        return "application/octet-stream";
+    };
+    let extension = name
+        .and_then(|n| std::path::Path::new(n).extension())
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("html" | "htm") => "text/html",
+        Some("rtf") => "text/rtf",
+        Some("svg") => "image/svg+xml",
+        _ if text.trim_start().starts_with("<svg") => "image/svg+xml",
+        _ => "text/plain",
+    }
+}
+
+/// The type of an image whose header parses, not just whose first bytes
+/// match: `BM` or `P3` may as well start a line of text. Formats this build
+/// cannot decode are taken on their signature only if it is a long one.
+#[cfg(feature = "image")]
+fn image_magic(data: &[u8]) -> Option<&'static str> {
+    use image::ImageFormat;
+    let reader = image::ImageReader::new(std::io::Cursor::new(data))
+        .with_guessed_format()
+        .ok()?;
+    let format = reader.format()?;
+    let short_magic = matches!(
+        format,
+        ImageFormat::Bmp
+            | ImageFormat::Pnm
+            | ImageFormat::Dds
+            | ImageFormat::Qoi
+            | ImageFormat::Ico
+    );
+    let trusted = if format.reading_enabled() {
+        reader.into_dimensions().is_ok()
+    } else {
+        !short_magic
+    };
+    trusted.then(|| format.to_mime_type())
+}
+
+/// The common image signatures, for builds without the `image` feature.
+/// Short ones are only trusted with a plausible header behind them.
+#[cfg(not(feature = "image"))]
+fn image_magic(data: &[u8]) -> Option<&'static str> {
+    const SIGNATURES: [(&[u8], &str); 8] = [
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"BM", "image/bmp"),
+        (b"II*\0", "image/tiff"),
+        (b"MM\0*", "image/tiff"),
+        (b"RIFF", "image/webp"),
+    ];
+    let header_ok = |mime| match mime {
+        "image/webp" => data.get(8..12) == Some(b"WEBP"),
+        // The DIB header has one of a handful of sizes.
+        "image/bmp" => data
+            .get(14..18)
+            .is_some_and(|size| matches!(size, [12 | 40 | 52 | 56 | 64 | 108 | 124, 0, 0, 0])),
+        _ => true,
+    };
+    SIGNATURES
+        .iter()
+        .find(|&&(magic, mime)| data.starts_with(magic) && header_ok(mime))
+        .map(|&(_, mime)| mime)
+}
+
+/// Why `wl-copy` cannot take this copy, if it cannot.
+fn copy_backend() -> Result<(), String> {
+    #[cfg(feature = "x11")]
+    if wsl::prefer_x11() {
+        return Err("copying needs wl-copy; the X11 backend is read-only".to_owned());
+    }
+    wsl::wayland_unavailable().map_or(Ok(()), |reason| Err(format!("wl-copy failed: {reason}")))
 }
 
 fn write_stdout(data: &[u8]) -> i32 {
@@ -282,9 +1349,9 @@ mod tests {
 
     /// Build the smallest `BI_RGB` BMP we can round-trip through `image`.
     /// Two pixels wide, one tall, 24 bits per pixel, no compression. Paired
-    /// with `tiny_bmp_bitfields` below, which exercises the variant `WSLg`
+    /// with `selftest::bitfields_bmp`, which exercises the variant `WSLg`
     /// actually delivers.
-    fn tiny_bmp() -> Vec<u8> {
+    pub fn tiny_bmp() -> Vec<u8> {
         let file_size: u32 = 58;
         let data_offset: u32 = 54;
         let header_size: u32 = 40;
@@ -315,77 +1382,127 @@ mod tests {
     }
 
     #[test]
-    fn bmp_round_trips_to_png() {
-        let bmp = tiny_bmp();
-        assert_eq!(&bmp[0..2], b"BM");
-        let img = image::load_from_memory(&bmp).expect("load BMP");
-        let mut out = Cursor::new(Vec::new());
-        img.write_to(&mut out, ImageFormat::Png)
-            .expect("encode PNG");
-        let png = out.into_inner();
+    fn selection_names_accept_prefixes() {
+        assert_eq!(Selection::parse("clipboard"), Some(Selection::Clipboard));
+        assert_eq!(Selection::parse("c"), Some(Selection::Clipboard));
+        assert_eq!(Selection::parse("prim"), Some(Selection::Primary));
+        assert_eq!(Selection::parse("secondary"), None);
+        assert_eq!(Selection::parse(""), None);
+    }
+
+    #[test]
+    fn raw_copies_are_stored_as_given() {
+        let utf16 = b"\xff\xfeh\0i\0";
+        let mut data = utf16.to_vec();
         assert_eq!(
-            &png[0..8],
-            &[0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A]
+            copy_form(&mut data, "text/plain", false, true),
+            "text/plain"
         );
+        assert_eq!(data, utf16);
+        let mut bmp = tiny_bmp();
+        assert_eq!(copy_form(&mut bmp, "image/bmp", false, true), "image/bmp");
+        assert_eq!(bmp, tiny_bmp());
+
+        assert_eq!(
+            copy_form(&mut data, "text/plain", false, false),
+            "text/plain;charset=utf-8"
+        );
+        assert_eq!(data, b"hi");
     }
 
-    /// Build a minimal 32-bpp `BI_BITFIELDS` BMP. This is the variant `WSLg`
-    /// delivers and the only reason this tool exists; the `BI_RGB` test
-    /// above only proves the wiring works. Guards against the `image` crate
-    /// regressing on `BI_BITFIELDS` decode.
-    fn tiny_bmp_bitfields() -> Vec<u8> {
-        let header_size: u32 = 40;
-        let data_offset: u32 = 14 + header_size + 12; // file header + DIB + masks
-        let file_size: u32 = data_offset + 8; // + 2 px * 4 bytes
-        let width: i32 = 2;
-        let height: i32 = 1;
-        let planes: u16 = 1;
-        let bpp: u16 = 32;
-        let bi_bitfields: u32 = 3;
-        let r_mask: u32 = 0x00FF_0000;
-        let g_mask: u32 = 0x0000_FF00;
-        let b_mask: u32 = 0x0000_00FF;
+    #[test]
+    fn ttls_must_fit_the_worker_in_milliseconds() {
+        assert_eq!(ttl_secs("30"), Some(30));
+        assert_eq!(ttl_secs("18446744073709551"), Some(18_446_744_073_709_551));
+        assert_eq!(ttl_secs("18446744073709552"), None);
+        assert_eq!(ttl_secs("0"), None);
+        assert_eq!(ttl_secs("-5"), None);
+    }
 
-        let mut bmp = Vec::with_capacity(file_size as usize);
-        bmp.extend_from_slice(b"BM");
-        bmp.extend_from_slice(&file_size.to_le_bytes());
-        bmp.extend_from_slice(&0u16.to_le_bytes());
-        bmp.extend_from_slice(&0u16.to_le_bytes());
-        bmp.extend_from_slice(&data_offset.to_le_bytes());
-        bmp.extend_from_slice(&header_size.to_le_bytes());
-        bmp.extend_from_slice(&width.to_le_bytes());
-        bmp.extend_from_slice(&height.to_le_bytes());
-        bmp.extend_from_slice(&planes.to_le_bytes());
-        bmp.extend_from_slice(&bpp.to_le_bytes());
-        bmp.extend_from_slice(&bi_bitfields.to_le_bytes());
-        bmp.extend_from_slice(&0u32.to_le_bytes()); // image size (0 ok for BI_BITFIELDS)
-        bmp.extend_from_slice(&0u32.to_le_bytes()); // x ppm
-        bmp.extend_from_slice(&0u32.to_le_bytes()); // y ppm
-        bmp.extend_from_slice(&0u32.to_le_bytes()); // colors used
-        bmp.extend_from_slice(&0u32.to_le_bytes()); // important colors
-        bmp.extend_from_slice(&r_mask.to_le_bytes());
-        bmp.extend_from_slice(&g_mask.to_le_bytes());
-        bmp.extend_from_slice(&b_mask.to_le_bytes());
-        // Two pixels (red, green) packed as little-endian u32 against the masks above.
-        bmp.extend_from_slice(&0x00FF_0000u32.to_le_bytes());
-        bmp.extend_from_slice(&0x0000_FF00u32.to_le_bytes());
-        bmp
+    #[test]
+    fn hex_colors_parse() {
+        assert_eq!(parse_hex_color("#fff"), Some([255, 255, 255]));
+        assert_eq!(parse_hex_color("20c0f0"), Some([0x20, 0xc0, 0xf0]));
+        assert_eq!(parse_hex_color("#12345"), None);
+    }
+
+    #[test]
+    fn structured_text_counts_as_text() {
+        assert!(is_text("text/csv"));
+        assert!(is_text("Application/JSON; charset=utf-8"));
+        assert!(is_text("application/ld+json"));
+        assert!(!is_text("application/pdf"));
+        assert!(!is_text("image/svg+xml"));
+    }
+
+    #[test]
+    fn binary_allowlist_patterns() {
+        assert!(mime_matches("application/pdf", "Application/PDF"));
+        assert!(mime_matches("application/*", "application/zip"));
+        assert!(!mime_matches("application/*", "image/png"));
+        assert!(!mime_matches("application/pdf", "application/zip"));
+    }
+
+    #[test]
+    fn mime_policy_blocks_before_allowing() {
+        assert!(policy_permits(None, None, "image/png"));
+        assert!(policy_permits(Some(" "), None, "image/png"));
+        assert!(!policy_permits(None, Some("image/*"), "image/png"));
+        assert!(policy_permits(Some("text/*, image/png"), None, "text/html"));
+        assert!(!policy_permits(Some("text/plain"), None, "image/png"));
+        assert!(!policy_permits(
+            Some("text/*"),
+            Some("text/html"),
+            "text/html;charset=utf-8"
+        ));
+    }
+
+    #[test]
+    fn piped_text_is_copied_as_text() {
+        for text in [&b"BMW is late\n"[..], b"P3 outage\n", b"qoif", b"<svg/>"] {
+            assert_eq!(input_mime(None, text, None), "text/plain");
+            assert_eq!(input_mime(Some("auto"), text, None), sniff_mime(text, None));
+        }
+        assert_eq!(sniff_mime(b"BMW is late\n", None), "text/plain");
+        assert_eq!(sniff_mime(b"P3 outage\n", None), "text/plain");
+        assert_eq!(input_mime(None, b"BMW", Some("notes.txt")), "text/plain");
+        assert_eq!(input_mime(Some("text/html"), b"BM", None), "text/html");
+    }
+
+    #[test]
+    fn copied_data_is_sniffed() {
+        assert_eq!(sniff_mime(&tiny_bmp(), Some("shot.txt")), "image/bmp");
+        // A 1x1 grayscale PNG.
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\0\x01\0\0\0\x01\x08\0\0\0\0\x3a\x7e\x9b\x55\0\0\0\nIDAT\x78\x9c\x63\x60\0\0\0\x02\0\x01\x48\xaf\xa4\x71\0\0\0\0IEND\xae\x42\x60\x82";
+        assert_eq!(sniff_mime(png, None), "image/png");
+        assert_eq!(sniff_mime(b"<p>hi</p>", Some("page.HTML")), "text/html");
+        assert_eq!(sniff_mime(b"hello", None), "text/plain");
+        assert_eq!(sniff_mime(b"\xff\xfeh\0", None), "text/plain");
+        assert_eq!(
+            sniff_mime(&[0xff, 0xfe, 0x00], None),
+            "application/octet-stream"
+        );
     }
 
     #[test]
-    fn bitfields_bmp_round_trips_to_png() {
-        let bmp = tiny_bmp_bitfields();
-        assert_eq!(&bmp[0..2], b"BM");
-        let img = image::load_from_memory(&bmp).expect("load BI_BITFIELDS BMP");
-        assert_eq!(img.width(), 2);
-        assert_eq!(img.height(), 1);
-        let mut out = Cursor::new(Vec::new());
-        img.write_to(&mut out, ImageFormat::Png)
-            .expect("encode PNG");
-        let png = out.into_inner();
+    fn utf16_is_only_decoded_when_utf8_was_asked_for() {
+        let utf16 = b"\xff\xfeh\0i\0".to_vec();
+        assert_eq!(utf16_to_utf8("text/plain", utf16.clone(), false), b"hi");
         assert_eq!(
-            &png[0..8],
-            &[0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A]
+            utf16_to_utf8("text/plain;charset=utf-8", utf16.clone(), true),
+            b"hi"
         );
+        assert_eq!(
+            utf16_to_utf8("text/plain;charset=utf-16", utf16.clone(), false),
+            utf16
+        );
+    }
+
+    #[test]
+    fn mime_matching_ignores_parameters_and_case() {
+        assert_eq!(base_mime("text/plain;charset=utf-8"), "text/plain");
+        assert_eq!(base_mime(" image/png "), "image/png");
+        assert!(same_base_mime("text/plain", "TEXT/plain; charset=UTF-8"));
+        assert!(!same_base_mime("text/plain", "text/html;charset=utf-8"));
     }
 }
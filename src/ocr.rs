@@ -0,0 +1,79 @@
+//! Text recognition for copied screenshots (error dialogs, terminals on the
+//! Windows side), via the `tesseract` command so no OCR engine is linked in.
+//!
+//! `-t "text/plain;x-ocr"` always recognizes the clipboard image. With
+//! `WSL_CLIP_BRIDGE_OCR=1`, plain `-t text/plain` does too when the
+//! clipboard holds only an image. Neither is advertised in TARGETS: a
+//! consumer that sees `text/plain` next to an image may stop asking for
+//! the image. Builds without the `image` feature have no PNG to hand
+//! tesseract, so both fail.
+
+#[cfg(feature = "image")]
+use std::io;
+#[cfg(feature = "image")]
+use std::time::Duration;
+
+#[cfg(feature = "image")]
+use crate::process::Cmd;
+
+#[cfg(feature = "image")]
+const OCR_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Whether `mime` carries the `x-ocr` parameter.
+pub fn requested(mime: &str) -> bool {
+    mime.split(';')
+        .skip(1)
+        .any(|param| param.trim().eq_ignore_ascii_case("x-ocr"))
+}
+
+/// The `text/plain` fallback for image-only copies (`WSL_CLIP_BRIDGE_OCR`).
+pub fn fallback_enabled() -> bool {
+    crate::settings::env_flag("WSL_CLIP_BRIDGE_OCR")
+}
+
+/// Recognize the text in `png`, in `WSL_CLIP_BRIDGE_OCR_LANG` (Tesseract
+/// language codes such as `eng+deu`; Tesseract's default otherwise).
+#[cfg(feature = "image")]
+pub fn recognize(png: &[u8]) -> Result<Vec<u8>, String> {
+    let lang =
+        crate::settings::setting("WSL_CLIP_BRIDGE_OCR_LANG").filter(|l| !l.trim().is_empty());
+    let mut args = vec!["stdin", "stdout"];
+    if let Some(lang) = &lang {
+        args.extend(["-l", lang.trim()]);
+    }
+    let mut text = Cmd::new("tesseract", OCR_TIMEOUT)
+        .args(&args)
+        .stdin(png)
+        .run()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => {
+                "OCR needs tesseract on PATH (apt install tesseract-ocr)".to_owned()
+            }
+            _ => format!("tesseract failed: {e}"),
+        })?;
+    // Tesseract ends each page with a form feed.
+    while text
+        .last()
+        .is_some_and(|b| matches!(b, b'\x0c' | b'\n' | b' '))
+    {
+        text.pop();
+    }
+    if text.is_empty() {
+        return Err("OCR found no text in the image".to_owned());
+    }
+    text.push(b'\n');
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ocr_is_requested_by_parameter() {
+        assert!(requested("text/plain;x-ocr"));
+        assert!(requested("text/plain; charset=utf-8; X-OCR"));
+        assert!(!requested("text/plain"));
+        assert!(!requested("text/x-ocr"));
+    }
+}
@@ -0,0 +1,152 @@
+//! OSC 52: hand copied text to the terminal emulator itself, which puts it
+//! on the clipboard of the machine the terminal runs on. Works through SSH
+//! and tmux/screen where neither `WSLg` nor an X server is reachable.
+
+use std::env;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+
+use crate::Selection;
+
+/// screen cannot pass a long DCS string through in one piece.
+const SCREEN_CHUNK: usize = 76;
+
+/// `WSL_CLIP_BRIDGE_OSC52`: `auto` (the default) only uses OSC 52 when
+/// `wl-copy` cannot be used or the session is remote.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Auto,
+    Always,
+    Never,
+}
+
+pub fn mode() -> Mode {
+    match crate::settings::setting("WSL_CLIP_BRIDGE_OSC52")
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+        .as_str()
+    {
+        "always" | "1" | "true" | "yes" | "on" => Mode::Always,
+        "never" | "0" | "false" | "no" | "off" => Mode::Never,
+        _ => Mode::Auto,
+    }
+}
+
+/// Whether a copy should also go out as OSC 52, given whether the
+/// `wl-copy` backend is usable.
+pub fn wanted(backend_available: bool) -> bool {
+    match mode() {
+        Mode::Always => true,
+        Mode::Never => false,
+        Mode::Auto => {
+            !backend_available
+                || env::var_os("SSH_TTY").is_some()
+                || env::var_os("SSH_CONNECTION").is_some()
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Multiplexer {
+    None,
+    Tmux,
+    Screen,
+}
+
+/// Write the sequence for `data` to the controlling terminal. Stdout is
+/// not used: it is usually a pipe, and the sequence must not end up in it.
+pub fn send(data: &[u8], sel: Selection) -> io::Result<()> {
+    let multiplexer = if env::var_os("TMUX").is_some() {
+        Multiplexer::Tmux
+    } else if env::var("TERM").is_ok_and(|t| t.starts_with("screen")) {
+        Multiplexer::Screen
+    } else {
+        Multiplexer::None
+    };
+    let mut tty = OpenOptions::new().write(true).open("/dev/tty")?;
+    tty.write_all(&sequence(data, sel, multiplexer))?;
+    tty.flush()
+}
+
+fn sequence(data: &[u8], sel: Selection, multiplexer: Multiplexer) -> Vec<u8> {
+    let target = match sel {
+        Selection::Clipboard => 'c',
+        Selection::Primary => 'p',
+    };
+    let osc = format!("\x1b]52;{target};{}\x07", base64(data));
+    match multiplexer {
+        Multiplexer::None => osc.into_bytes(),
+        // tmux forwards a DCS passthrough with every ESC doubled (needs
+        // `set -g allow-passthrough on` on tmux 3.3+).
+        Multiplexer::Tmux => {
+            format!("\x1bPtmux;{}\x1b\\", osc.replace('\x1b', "\x1b\x1b")).into_bytes()
+        }
+        Multiplexer::Screen => {
+            let mut out = Vec::with_capacity(osc.len() + osc.len() / SCREEN_CHUNK * 4 + 4);
+            for chunk in osc.as_bytes().chunks(SCREEN_CHUNK) {
+                out.extend_from_slice(b"\x1bP");
+                out.extend_from_slice(chunk);
+                out.extend_from_slice(b"\x1b\\");
+            }
+            out
+        }
+    }
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = u32::from_be_bytes([0, b[0], b[1], b[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(char::from(ALPHABET[(n >> (18 - 6 * i)) as usize & 63]));
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_matches_rfc_4648_vectors() {
+        let cases = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(base64(input.as_bytes()), expected);
+        }
+    }
+
+    #[test]
+    fn sequences_are_wrapped_for_multiplexers() {
+        assert_eq!(
+            sequence(b"hi", Selection::Clipboard, Multiplexer::None),
+            b"\x1b]52;c;aGk=\x07"
+        );
+        assert_eq!(
+            sequence(b"hi", Selection::Primary, Multiplexer::Tmux),
+            b"\x1bPtmux;\x1b\x1b]52;p;aGk=\x07\x1b\\"
+        );
+        let screen = sequence(&[b'x'; 100], Selection::Clipboard, Multiplexer::Screen);
+        assert!(screen.starts_with(b"\x1bP\x1b]52;c;"));
+        assert_eq!(screen.windows(2).filter(|w| w == b"\x1bP").count(), 2);
+    }
+}
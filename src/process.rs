@@ -0,0 +1,344 @@
+//! Child-process runner.
+//!
+//! Every external command the shim spawns goes through [`Cmd`] so the
+//! timeout, output capture, concurrency cap, and reaping rules live in one
+//! place instead of being re-implemented per call site.
+
+use std::ffi::OsStr;
+use std::io::{self, Read, Write};
+use std::os::fd::OwnedFd;
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Condvar, Mutex, PoisonError, mpsc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rustix::event::{PollFd, PollFlags, Timespec, poll};
+use rustix::process::{Pid, PidfdFlags, pidfd_open};
+
+/// How often to check on a child when the kernel offers no pidfd.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Upper bound on children alive at once across all threads of this process.
+const MAX_CHILDREN: usize = 4;
+
+static RUNNING: Mutex<usize> = Mutex::new(0);
+static SLOT_FREED: Condvar = Condvar::new();
+
+/// One external command invocation.
+pub struct Cmd<'a> {
+    program: &'a str,
+    args: Vec<&'a str>,
+    envs: Vec<(&'a str, &'a OsStr)>,
+    timeout: Duration,
+    stdin: Option<&'a [u8]>,
+    stdout: Output,
+}
+
+/// Where the child's stdout goes.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Output {
+    /// Returned by `run`; stderr is captured too.
+    Capture,
+    /// Both stdout and stderr go to `/dev/null`.
+    Discard,
+    /// Straight to our own stdout; stderr is still captured for errors.
+    Inherit,
+}
+
+impl<'a> Cmd<'a> {
+    pub const fn new(program: &'a str, timeout: Duration) -> Self {
+        Self {
+            program,
+            args: Vec::new(),
+            envs: Vec::new(),
+            timeout,
+            stdin: None,
+            stdout: Output::Capture,
+        }
+    }
+
+    pub fn args(mut self, args: &[&'a str]) -> Self {
+        self.args.extend_from_slice(args);
+        self
+    }
+
+    pub fn env(mut self, key: &'a str, value: &'a OsStr) -> Self {
+        self.envs.push((key, value));
+        self
+    }
+
+    /// Feed `data` to the child's stdin (from a separate thread, so a child
+    /// that writes before it finishes reading cannot deadlock us).
+    pub const fn stdin(mut self, data: &'a [u8]) -> Self {
+        self.stdin = Some(data);
+        self
+    }
+
+    /// Send the child's stdout and stderr to `/dev/null` instead of
+    /// capturing them. Required for commands like `wl-copy` that fork a
+    /// background server which would otherwise hold our pipes open.
+    pub const fn discard_output(mut self) -> Self {
+        self.stdout = Output::Discard;
+        self
+    }
+
+    /// Let the child write to our stdout directly, so its output is never
+    /// held in memory here; `run` then returns an empty buffer.
+    pub const fn inherit_stdout(mut self) -> Self {
+        self.stdout = Output::Inherit;
+        self
+    }
+
+    pub const fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Run to completion and return stdout. A non-zero exit becomes an error
+    /// carrying the trimmed stderr; exceeding the timeout kills the child.
+    pub fn run(self) -> io::Result<Vec<u8>> {
+        let _slot = Slot::acquire();
+        let output = || {
+            if self.stdout == Output::Discard {
+                Stdio::null()
+            } else {
+                Stdio::piped()
+            }
+        };
+        let mut cmd = Command::new(self.program);
+        cmd.args(&self.args)
+            .envs(self.envs.iter().copied())
+            .stdin(if self.stdin.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(if self.stdout == Output::Inherit {
+                Stdio::inherit()
+            } else {
+                output()
+            })
+            .stderr(output());
+
+        let mut child = Reaper(cmd.spawn()?);
+        let stdout_rx = drain(child.0.stdout.take());
+        let stderr_rx = drain(child.0.stderr.take());
+        let child_stdin = child.0.stdin.take();
+
+        thread::scope(|scope| {
+            if let (Some(mut pipe), Some(data)) = (child_stdin, self.stdin) {
+                // A write error means the child exited early; its exit status
+                // tells the real story.
+                scope.spawn(move || {
+                    let _ = pipe.write_all(data);
+                });
+            }
+            self.wait(&mut child, &stdout_rx, &stderr_rx)
+        })
+    }
+
+    /// Start the child and return without waiting, for helpers that must
+    /// outlive this process. Output is discarded, stdin (if any) is written
+    /// before returning, and the child gets its own process group so the
+    /// caller's Ctrl+C does not reach it. It is not reaped here: once we exit
+    /// it is re-parented to init, which does. The timeout does not apply.
+    pub fn detach(self) -> io::Result<()> {
+        let _slot = Slot::acquire();
+        let mut child = Command::new(self.program)
+            .args(&self.args)
+            .envs(self.envs.iter().copied())
+            .stdin(if self.stdin.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .process_group(0)
+            .spawn()?;
+        if let (Some(mut pipe), Some(data)) = (child.stdin.take(), self.stdin) {
+            pipe.write_all(data)?;
+        }
+        Ok(())
+    }
+
+    fn wait(
+        &self,
+        child: &mut Reaper,
+        stdout_rx: &mpsc::Receiver<Vec<u8>>,
+        stderr_rx: &mpsc::Receiver<Vec<u8>>,
+    ) -> io::Result<Vec<u8>> {
+        let deadline = Instant::now().checked_add(self.timeout);
+        let exited = ExitSignal::new(&child.0);
+        let timed_out = || {
+            io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("{} timed out after {:?}", self.program, self.timeout),
+            )
+        };
+        // A background job the child started can hold its pipes open long
+        // after it exits; stop reading them at the deadline all the same.
+        let collect = |rx: &mpsc::Receiver<Vec<u8>>| {
+            let remaining = deadline.map_or(Duration::MAX, |d| {
+                d.saturating_duration_since(Instant::now())
+            });
+            match rx.recv_timeout(remaining) {
+                Err(mpsc::RecvTimeoutError::Timeout) => Err(timed_out()),
+                received => Ok(received.unwrap_or_default()),
+            }
+        };
+        loop {
+            if let Some(status) = child.0.try_wait()? {
+                let stdout = collect(stdout_rx)?;
+                let stderr = collect(stderr_rx)?;
+                if status.success() {
+                    return Ok(stdout);
+                }
+                let stderr_text = String::from_utf8_lossy(&stderr);
+                let trimmed = stderr_text.trim();
+                return Err(io::Error::other(if trimmed.is_empty() {
+                    format!("{} exited with {status}", self.program)
+                } else {
+                    format!("{} exited with {status}: {trimmed}", self.program)
+                }));
+            }
+            let remaining = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+            if remaining == Some(Duration::ZERO) {
+                // Kill here rather than leaving it to the reaper: a stdin
+                // writer blocked on a full pipe only returns once the child
+                // is gone, and the scope cannot end before it does.
+                let _ = child.0.kill();
+                return Err(timed_out());
+            }
+            exited.wait(remaining);
+        }
+    }
+}
+
+/// Wakes `Cmd::wait` the moment the child exits, via a pidfd, which
+/// becomes readable on exit. Kernels without pidfd support (before 5.3,
+/// and WSL1) fall back to polling every 50 ms.
+struct ExitSignal(Option<OwnedFd>);
+
+impl ExitSignal {
+    fn new(child: &Child) -> Self {
+        Self(pidfd_open(Pid::from_child(child), PidfdFlags::empty()).ok())
+    }
+
+    /// Block until the child may have exited or `limit` (`None`: no limit)
+    /// passes. Callers re-check with `try_wait`, so an early return is
+    /// harmless.
+    fn wait(&self, limit: Option<Duration>) {
+        let Some(pidfd) = &self.0 else {
+            thread::sleep(limit.map_or(POLL_INTERVAL, |l| l.min(POLL_INTERVAL)));
+            return;
+        };
+        let timeout = limit.and_then(|l| Timespec::try_from(l).ok());
+        let _ = poll(&mut [PollFd::new(pidfd, PollFlags::IN)], timeout.as_ref());
+    }
+}
+
+/// Read a child pipe to EOF on a dedicated thread so a full kernel pipe
+/// buffer can never stall the child.
+fn drain(pipe: Option<impl Read + Send + 'static>) -> mpsc::Receiver<Vec<u8>> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        let _ = tx.send(buf);
+    });
+    rx
+}
+
+/// Owns a child and guarantees it is killed and reaped on every exit path,
+/// including early returns via `?`, so no zombie outlives the call.
+struct Reaper(Child);
+
+impl Drop for Reaper {
+    fn drop(&mut self) {
+        if matches!(self.0.try_wait(), Ok(None)) {
+            let _ = self.0.kill();
+        }
+        let _ = self.0.wait();
+    }
+}
+
+/// A held slot in the global child budget. Blocks in `acquire` while
+/// `MAX_CHILDREN` are already running; released on drop.
+struct Slot;
+
+impl Slot {
+    fn acquire() -> Self {
+        let mut running = RUNNING.lock().unwrap_or_else(PoisonError::into_inner);
+        while *running >= MAX_CHILDREN {
+            running = SLOT_FREED
+                .wait(running)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        *running += 1;
+        Self
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        *RUNNING.lock().unwrap_or_else(PoisonError::into_inner) -= 1;
+        SLOT_FREED.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_and_timeout_are_noticed_promptly() {
+        let start = Instant::now();
+        let out = Cmd::new("sh", Duration::from_secs(5))
+            .args(&["-c", "echo hi"])
+            .run()
+            .expect("run sh");
+        assert_eq!(out, b"hi\n");
+        let err = Cmd::new("sleep", Duration::from_millis(100))
+            .args(&["5"])
+            .run()
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(
+            start.elapsed() < Duration::from_secs(2),
+            "{:?}",
+            start.elapsed()
+        );
+    }
+
+    #[test]
+    fn background_jobs_holding_the_pipes_do_not_outlive_the_timeout() {
+        let start = Instant::now();
+        let err = Cmd::new("sh", Duration::from_millis(200))
+            .args(&["-c", "sleep 5 & echo started"])
+            .run()
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(
+            start.elapsed() < Duration::from_secs(2),
+            "{:?}",
+            start.elapsed()
+        );
+    }
+
+    /// Far more than a 64 KiB pipe buffer in both directions: a child that
+    /// writes before it has read everything must not stall.
+    #[test]
+    fn large_payloads_pass_through_both_pipes() {
+        let input: Vec<u8> = (0..8u32 << 20).map(|i| (i % 251) as u8).collect();
+        let out = Cmd::new("sh", Duration::from_secs(20))
+            .args(&["-c", "head -c 4194304 /dev/zero >&2; cat"])
+            .stdin(&input)
+            .run()
+            .expect("run sh");
+        assert!(out == input, "got {} of {} bytes", out.len(), input.len());
+    }
+}
@@ -0,0 +1,117 @@
+//! Named bundles of image settings, picked with `--profile <name>` or
+//! `WSL_CLIP_BRIDGE_PROFILE`. A profile only supplies defaults: any
+//! `WSL_CLIP_BRIDGE_*` variable that is set still wins, so switching between
+//! API-upload and pixel-perfect pastes is one flag instead of a handful of
+//! exports.
+
+use std::env;
+use std::sync::OnceLock;
+
+type Settings = &'static [(&'static str, &'static str)];
+
+const PROFILES: [(&str, Settings); 3] = [
+    // The Anthropic API takes images up to 5 MB after base64, which inflates
+    // by a third; sRGB avoids relying on the model honoring ICC profiles.
+    (
+        "claude",
+        &[
+            ("WSL_CLIP_BRIDGE_MAX_IMAGE_BYTES", "3750000"),
+            ("WSL_CLIP_BRIDGE_ICC", "srgb"),
+            ("WSL_CLIP_BRIDGE_JPEG_QUALITY", "85"),
+        ],
+    ),
+    (
+        "lossless",
+        &[
+            ("WSL_CLIP_BRIDGE_MAX_IMAGE_BYTES", "0"),
+            ("WSL_CLIP_BRIDGE_ICC", "keep"),
+            ("WSL_CLIP_BRIDGE_JPEG_QUALITY", "100"),
+            ("WSL_CLIP_BRIDGE_PNG_COMPRESSION", "best"),
+        ],
+    ),
+    (
+        "fast",
+        &[
+            ("WSL_CLIP_BRIDGE_MAX_IMAGE_BYTES", "0"),
+            ("WSL_CLIP_BRIDGE_ICC", "strip"),
+            ("WSL_CLIP_BRIDGE_PNG_COMPRESSION", "fast"),
+        ],
+    ),
+];
+
+static SELECTED: OnceLock<Option<(&str, Settings)>> = OnceLock::new();
+
+fn lookup(name: &str) -> Result<(&'static str, Settings), String> {
+    PROFILES
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name.trim()))
+        .copied()
+        .ok_or_else(|| {
+            let known: Vec<&str> = PROFILES.iter().map(|(n, _)| *n).collect();
+            format!("unknown profile {name} (known: {})", known.join(", "))
+        })
+}
+
+/// Activate the profile named by `--profile`, overriding
+/// `WSL_CLIP_BRIDGE_PROFILE`. Must run before any setting is read.
+pub fn select(name: &str) -> Result<(), String> {
+    let profile = lookup(name)?;
+    let _ = SELECTED.set(Some(profile));
+    Ok(())
+}
+
+fn selected() -> Option<(&'static str, Settings)> {
+    *SELECTED.get_or_init(|| {
+        let name = env::var("WSL_CLIP_BRIDGE_PROFILE").ok()?;
+        if name.trim().is_empty() {
+            return None;
+        }
+        // An unknown name is reported by settings::problems().
+        lookup(&name).ok()
+    })
+}
+
+pub fn exists(name: &str) -> bool {
+    lookup(name).is_ok()
+}
+
+/// The name of the active profile, if any.
+pub fn active() -> Option<&'static str> {
+    selected().map(|(name, _)| name)
+}
+
+/// The active profile's value for `variable`, if it sets one.
+pub fn default_for(variable: &str) -> Option<&'static str> {
+    selected()?
+        .1
+        .iter()
+        .find(|(key, _)| *key == variable)
+        .map(|&(_, value)| value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_are_found_by_name() {
+        assert!(lookup("Claude").is_ok_and(|(_, s)| {
+            s.iter()
+                .any(|(k, v)| *k == "WSL_CLIP_BRIDGE_MAX_IMAGE_BYTES" && *v == "3750000")
+        }));
+        let err = lookup("tiny").unwrap_err();
+        assert!(err.contains("claude, lossless, fast"), "{err}");
+    }
+
+    #[test]
+    fn profiles_only_set_known_settings() {
+        for (name, settings) in PROFILES {
+            for (key, _) in settings {
+                assert!(
+                    crate::settings::SETTINGS.iter().any(|s| s.name == *key),
+                    "{name} sets unknown {key}"
+                );
+            }
+        }
+    }
+}
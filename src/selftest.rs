@@ -0,0 +1,259 @@
+//! `--self-test`: a quick end-to-end check of everything a paste depends on,
+//! printed as one pass/fail line per step.
+
+use std::io;
+
+#[cfg(feature = "image")]
+use crate::images::encode_png;
+#[cfg(feature = "image")]
+use crate::ocr;
+use crate::{
+    Selection, is_transient, list_types, settings, wl_cmd, wl_copy, wl_fetch, wl_list_types, wsl,
+};
+
+/// Run every check and return the process exit code: 0 only if all passed.
+pub fn run() -> i32 {
+    let checks = [
+        ("environment", check_environment()),
+        ("settings", check_settings()),
+        ("clipboard backend", check_backend()),
+        ("wl-copy", check_wl_copy()),
+        ("copy -> TARGETS -> paste -> clear", check_round_trip()),
+        #[cfg(feature = "image")]
+        ("tesseract", check_tesseract()),
+        #[cfg(feature = "image")]
+        ("BI_BITFIELDS BMP -> PNG", check_conversion()),
+    ];
+    let mut failed = 0;
+    for (name, result) in checks {
+        match result {
+            Ok(detail) => println!("ok    {name}: {detail}"),
+            Err(detail) => {
+                println!("FAIL  {name}: {detail}");
+                failed += 1;
+            }
+        }
+    }
+    i32::from(failed > 0)
+}
+
+fn check_environment() -> Result<String, String> {
+    let env = wsl::environment();
+    let summary = format!(
+        "{:?}, WSLg {}, Wayland socket {}",
+        env.kernel,
+        if env.wslg { "mounted" } else { "absent" },
+        env.wayland_socket
+            .as_deref()
+            .map_or_else(|| "missing".to_owned(), |p| p.display().to_string())
+    );
+    environment_verdict(summary, wsl::wayland_unavailable(), uses_x11())
+}
+
+/// Wayland being unusable is only a failure when X11 is not answering
+/// instead.
+fn environment_verdict(
+    summary: String,
+    wayland_unavailable: Option<&str>,
+    x11: bool,
+) -> Result<String, String> {
+    match wayland_unavailable {
+        Some(reason) if x11 => Ok(format!("{summary}; backend: X11 (Wayland: {reason})")),
+        Some(reason) => Err(format!("{summary}: {reason}")),
+        None => Ok(summary),
+    }
+}
+
+/// Whether the X11 backend answers instead of `wl-paste`.
+#[cfg(feature = "x11")]
+fn uses_x11() -> bool {
+    wsl::prefer_x11()
+}
+
+#[cfg(not(feature = "x11"))]
+const fn uses_x11() -> bool {
+    false
+}
+
+fn check_settings() -> Result<String, String> {
+    let problems = settings::problems();
+    if problems.is_empty() {
+        Ok("every WSL_CLIP_BRIDGE_* variable is known and valid".to_owned())
+    } else {
+        Err(problems.join("; "))
+    }
+}
+
+fn check_wl_copy() -> Result<String, String> {
+    if uses_x11() {
+        return Ok("skipped: not used by the read-only X11 backend".to_owned());
+    }
+    wsl::find_in_path("wl-copy")
+        .map(|path| path.display().to_string())
+        .ok_or_else(|| "not found on PATH; copying needs wl-clipboard".to_owned())
+}
+
+/// Only a failure when OCR is switched on; `x-ocr` requests report a
+/// missing tesseract themselves.
+#[cfg(feature = "image")]
+fn check_tesseract() -> Result<String, String> {
+    match wsl::find_in_path("tesseract") {
+        Some(path) => Ok(path.display().to_string()),
+        None if ocr::fallback_enabled() => Err(
+            "not found on PATH but WSL_CLIP_BRIDGE_OCR is on (apt install tesseract-ocr)"
+                .to_owned(),
+        ),
+        None => Ok("not installed; only needed for OCR".to_owned()),
+    }
+}
+
+fn check_backend() -> Result<String, String> {
+    match list_types(Selection::Clipboard) {
+        Ok(types) => Ok(format!("responded, {} type(s) offered", types.len())),
+        // The backend is reachable, the clipboard is just empty.
+        Err(e) if is_empty_clipboard(&e) => Ok(format!("responded ({e})")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// `wl-paste`'s and the X11 backend's ways of saying nothing usable is
+/// copied, as opposed to the backend being unreachable.
+fn is_empty_clipboard(e: &io::Error) -> bool {
+    let message = e.to_string();
+    !is_transient(e)
+        && [
+            "Nothing is copied",
+            "No suitable type",
+            "cannot provide that type",
+        ]
+        .iter()
+        .any(|m| message.contains(m))
+}
+
+/// Copy a token to the primary selection with `wl-copy`, check that
+/// `wl-paste` lists and returns it, then clear it again. The primary
+/// selection is used so the clipboard proper is left alone.
+fn check_round_trip() -> Result<String, String> {
+    if uses_x11() {
+        return Ok("skipped: the X11 backend is read-only".to_owned());
+    }
+    let sel = Selection::Primary;
+    let mime = "text/plain;charset=utf-8";
+    let token = format!("wsl-clip-bridge self-test {}", std::process::id());
+    wl_copy(token.as_bytes(), mime, sel).map_err(|e| format!("copy failed: {e}"))?;
+    let checked = wl_list_types(sel)
+        .map_err(|e| format!("TARGETS failed: {e}"))
+        .and_then(|types| {
+            if types.iter().any(|t| t == mime) {
+                wl_fetch(mime, sel).map_err(|e| format!("paste failed: {e}"))
+            } else {
+                Err(format!("TARGETS lists {types:?}, not {mime}"))
+            }
+        })
+        .and_then(|pasted| {
+            if pasted == token.as_bytes() {
+                Ok(())
+            } else {
+                Err(format!(
+                    "pasted {:?}, expected {token:?}",
+                    String::from_utf8_lossy(&pasted)
+                ))
+            }
+        });
+    let cleared = wl_cmd("wl-copy")
+        .args(&["--clear", "--primary"])
+        .discard_output()
+        .run();
+    checked?;
+    cleared.map_err(|e| format!("clear failed: {e}"))?;
+    Ok(format!("{} bytes via the primary selection", token.len()))
+}
+
+#[cfg(feature = "image")]
+fn check_conversion() -> Result<String, String> {
+    let png = encode_png(&bitfields_bmp())?;
+    let img = image::load_from_memory_with_format(&png, image::ImageFormat::Png)
+        .map_err(|e| format!("re-decoding the PNG failed: {e}"))?;
+    if (img.width(), img.height()) == (2, 1) {
+        Ok(format!("{} byte PNG, 2x1", png.len()))
+    } else {
+        Err(format!(
+            "expected 2x1, got {}x{}",
+            img.width(),
+            img.height()
+        ))
+    }
+}
+
+/// Build a minimal 32-bpp `BI_BITFIELDS` BMP. This is the variant `WSLg`
+/// delivers and the only reason this tool exists. Used by `--self-test` and
+/// by the unit tests to guard against the `image` crate regressing on
+/// `BI_BITFIELDS` decode.
+#[cfg(feature = "image")]
+pub fn bitfields_bmp() -> Vec<u8> {
+    let header_size: u32 = 40;
+    let data_offset: u32 = 14 + header_size + 12; // file header + DIB + masks
+    let file_size: u32 = data_offset + 8; // + 2 px * 4 bytes
+    let width: i32 = 2;
+    let height: i32 = 1;
+    let planes: u16 = 1;
+    let bpp: u16 = 32;
+    let bi_bitfields: u32 = 3;
+    let r_mask: u32 = 0x00FF_0000;
+    let g_mask: u32 = 0x0000_FF00;
+    let b_mask: u32 = 0x0000_00FF;
+
+    let mut bmp = Vec::with_capacity(file_size as usize);
+    bmp.extend_from_slice(b"BM");
+    bmp.extend_from_slice(&file_size.to_le_bytes());
+    bmp.extend_from_slice(&0u16.to_le_bytes());
+    bmp.extend_from_slice(&0u16.to_le_bytes());
+    bmp.extend_from_slice(&data_offset.to_le_bytes());
+    bmp.extend_from_slice(&header_size.to_le_bytes());
+    bmp.extend_from_slice(&width.to_le_bytes());
+    bmp.extend_from_slice(&height.to_le_bytes());
+    bmp.extend_from_slice(&planes.to_le_bytes());
+    bmp.extend_from_slice(&bpp.to_le_bytes());
+    bmp.extend_from_slice(&bi_bitfields.to_le_bytes());
+    bmp.extend_from_slice(&0u32.to_le_bytes()); // image size (0 ok for BI_BITFIELDS)
+    bmp.extend_from_slice(&0u32.to_le_bytes()); // x ppm
+    bmp.extend_from_slice(&0u32.to_le_bytes()); // y ppm
+    bmp.extend_from_slice(&0u32.to_le_bytes()); // colors used
+    bmp.extend_from_slice(&0u32.to_le_bytes()); // important colors
+    bmp.extend_from_slice(&r_mask.to_le_bytes());
+    bmp.extend_from_slice(&g_mask.to_le_bytes());
+    bmp.extend_from_slice(&b_mask.to_le_bytes());
+    // Two pixels (red, green) packed as little-endian u32 against the masks above.
+    bmp.extend_from_slice(&0x00FF_0000u32.to_le_bytes());
+    bmp.extend_from_slice(&0x0000_FF00u32.to_le_bytes());
+    bmp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_wayland_is_fine_when_x11_answers() {
+        let summary = || "Wsl1, WSLg absent, Wayland socket missing".to_owned();
+        let reason = Some("WSL1 has no WSLg clipboard");
+        let ok = environment_verdict(summary(), reason, true).expect("X11 setup passes");
+        assert!(
+            ok.contains("backend: X11") && ok.contains("WSL1 has no"),
+            "{ok}"
+        );
+        assert!(environment_verdict(summary(), reason, false).is_err());
+        assert_eq!(environment_verdict(summary(), None, false), Ok(summary()));
+    }
+
+    #[test]
+    fn only_an_empty_clipboard_counts_as_a_working_backend() {
+        let empty = io::Error::other("wl-paste exited with exit status: 1: Nothing is copied");
+        assert!(is_empty_clipboard(&empty));
+        let down = io::Error::other(
+            "wl-paste exited with exit status: 1: Failed to connect to a Wayland server",
+        );
+        assert!(!is_empty_clipboard(&down));
+        assert!(!is_empty_clipboard(&io::Error::other("Connection refused")));
+    }
+}
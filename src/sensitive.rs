@@ -0,0 +1,109 @@
+//! Copies that must not linger: `-i --sensitive`, and clipboard contents a
+//! password manager marked with `x-kde-passwordManagerHint`.
+//!
+//! A sensitive copy skips the copy hook (which would write the payload to a
+//! temp file) and OSC 52 (which would put it where it cannot be cleared),
+//! and is cleared from the clipboard after a short TTL by a detached copy
+//! of this binary. The worker only receives a fingerprint of
+//! the secret and clears the clipboard only if it still holds the same data,
+//! so a newer copy is never wiped. Buffers that held a secret are zeroed
+//! before they are freed.
+//!
+//! `-i --ttl <secs>` uses the same worker to expire an ordinary copy.
+
+use std::env;
+use std::hash::{DefaultHasher, Hasher};
+use std::io::{self, Read};
+use std::thread;
+use std::time::Duration;
+
+use zeroize::Zeroize;
+
+use crate::process::Cmd;
+use crate::{Selection, fetch_matching};
+
+/// The type KDE Klipper and wl-clipboard use to flag password-manager
+/// copies; its content is `secret`.
+pub const HINT: &str = "x-kde-passwordManagerHint";
+
+const SENSITIVE_TTL: Duration = Duration::from_secs(30);
+const SPAWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long a sensitive copy stays on the clipboard
+/// (`WSL_CLIP_BRIDGE_SENSITIVE_TTL_SECS`).
+pub fn ttl() -> Duration {
+    crate::settings::env_u64("WSL_CLIP_BRIDGE_SENSITIVE_TTL_SECS")
+        .filter(|secs| secs.checked_mul(1000).is_some())
+        .map_or(SENSITIVE_TTL, Duration::from_secs)
+}
+
+/// Whether the current clipboard contents were marked as a secret by the
+/// application that copied them.
+pub fn is_marked(sel: Selection) -> bool {
+    crate::list_types(sel).is_ok_and(|types| types.iter().any(|t| t == HINT))
+        && crate::fetch(HINT, sel).is_ok_and(|v| v.trim_ascii() == b"secret")
+}
+
+/// `DefaultHasher::new()` uses fixed keys, so the worker, being the same
+/// binary, computes the same value.
+pub fn fingerprint(data: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(data);
+    hasher.finish()
+}
+
+/// Start the worker that clears `data` from the clipboard after `delay`.
+/// The fingerprint goes over stdin so it never shows up in `ps`.
+pub fn schedule_clear(data: &[u8], mime: &str, sel: Selection, delay: Duration) -> io::Result<()> {
+    let exe = env::current_exe()?;
+    let exe = exe.to_str().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "executable path is not UTF-8")
+    })?;
+    let millis = u64::try_from(delay.as_millis())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "expiry too far away"))?
+        .to_string();
+    let fingerprint = fingerprint(data).to_string();
+    Cmd::new(exe, SPAWN_TIMEOUT)
+        .args(&[
+            "--clear-after",
+            &millis,
+            "-t",
+            mime,
+            "-selection",
+            sel.name(),
+        ])
+        .stdin(fingerprint.as_bytes())
+        .detach()
+}
+
+/// Worker side of [`schedule_clear`]: wait, then clear the selection if it
+/// still holds the data whose fingerprint arrives on stdin.
+pub fn clear_after(delay: Duration, mime: &str, sel: Selection) -> i32 {
+    let mut expected = String::new();
+    if io::stdin().read_to_string(&mut expected).is_err() {
+        return 1;
+    }
+    let Ok(expected) = expected.trim().parse::<u64>() else {
+        return 1;
+    };
+    thread::sleep(delay);
+    let unchanged = fetch_matching(mime, sel).is_ok_and(|mut data| {
+        let same = fingerprint(&data) == expected;
+        data.zeroize();
+        same
+    });
+    if !unchanged {
+        return 0;
+    }
+    let mut args = vec!["--clear"];
+    if sel == Selection::Primary {
+        args.push("--primary");
+    }
+    i32::from(
+        crate::wl_cmd("wl-copy")
+            .args(&args)
+            .discard_output()
+            .run()
+            .is_err(),
+    )
+}
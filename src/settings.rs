@@ -0,0 +1,273 @@
+//! Every `WSL_CLIP_BRIDGE_*` setting this binary reads, with its built-in
+//! default, and the one place their values are resolved: the environment,
+//! then the active profile, then the default. `--config` prints the value
+//! each one resolves to and where that value came from.
+
+use std::env;
+use std::fmt::Write as _;
+
+use crate::profile;
+
+/// A setting, the default used when neither the environment nor the active
+/// profile sets it (empty: off or unset), and what a valid value looks like.
+pub struct Setting {
+    pub name: &'static str,
+    pub default: &'static str,
+    valid: fn(&str) -> bool,
+    expected: &'static str,
+}
+
+const fn entry(
+    name: &'static str,
+    default: &'static str,
+    valid: fn(&str) -> bool,
+    expected: &'static str,
+) -> Setting {
+    Setting {
+        name,
+        default,
+        valid,
+        expected,
+    }
+}
+
+const ANY: fn(&str) -> bool = |_| true;
+const NUMBER: fn(&str) -> bool = |v| v.trim().parse::<u64>().is_ok();
+const FLAG: fn(&str) -> bool = |v| {
+    matches!(
+        v.trim().to_ascii_lowercase().as_str(),
+        "1" | "true" | "yes" | "on" | "0" | "false" | "no" | "off"
+    )
+};
+
+fn one_of(value: &str, choices: &[&str]) -> bool {
+    choices.contains(&value.trim().to_ascii_lowercase().as_str())
+}
+
+pub const SETTINGS: [Setting; 26] = [
+    entry(
+        "WSL_CLIP_BRIDGE_PROFILE",
+        "",
+        |v| v.trim().is_empty() || profile::exists(v),
+        "claude, lossless, or fast",
+    ),
+    entry("WSL_CLIP_BRIDGE_WL_TIMEOUT_MS", "5000", NUMBER, "a number"),
+    entry("WSL_CLIP_BRIDGE_WL_RETRIES", "0", NUMBER, "a number"),
+    entry(
+        "WSL_CLIP_BRIDGE_WATCH_INTERVAL_MS",
+        "500",
+        NUMBER,
+        "a number",
+    ),
+    entry("WSL_CLIP_BRIDGE_ON_COPY", "", ANY, ""),
+    entry("WSL_CLIP_BRIDGE_ON_PASTE", "", ANY, ""),
+    entry(
+        "WSL_CLIP_BRIDGE_HOOK_TIMEOUT_MS",
+        "10000",
+        NUMBER,
+        "a number",
+    ),
+    entry(
+        "WSL_CLIP_BRIDGE_OSC52",
+        "auto",
+        |v| one_of(v, &["auto", "always", "never", ""]) || FLAG(v),
+        "auto, always, or never",
+    ),
+    entry("WSL_CLIP_BRIDGE_ALLOW_BINARY_MIME", "", ANY, ""),
+    entry("WSL_CLIP_BRIDGE_ALLOWED_MIME", "", ANY, ""),
+    entry("WSL_CLIP_BRIDGE_BLOCKED_MIME", "", ANY, ""),
+    entry("WSL_CLIP_BRIDGE_TRANSCODE", "1", FLAG, "1 or 0"),
+    entry(
+        "WSL_CLIP_BRIDGE_BACKGROUND",
+        "#ffffff",
+        |v| crate::parse_hex_color(v).is_some(),
+        "#rrggbb or #rgb",
+    ),
+    entry(
+        "WSL_CLIP_BRIDGE_JPEG_QUALITY",
+        "75",
+        |v| v.trim().parse::<u8>().is_ok_and(|q| (1..=100).contains(&q)),
+        "1 to 100",
+    ),
+    entry(
+        "WSL_CLIP_BRIDGE_PNG_COMPRESSION",
+        "fast",
+        |v| one_of(v, &["fast", "default", "best"]) || v.trim().parse::<u8>().is_ok_and(|l| l <= 9),
+        "fast, default, best, or 0 to 9",
+    ),
+    entry(
+        "WSL_CLIP_BRIDGE_MAX_IMAGE_BYTES",
+        "0",
+        |v| {
+            v.split(',').all(|e| {
+                let bytes = e.split_once('=').map_or(e, |(_, n)| n);
+                NUMBER(bytes)
+            })
+        },
+        "a number, optionally with <type>=<number> entries",
+    ),
+    entry("WSL_CLIP_BRIDGE_DECODE_LIMIT_MB", "512", NUMBER, "a number"),
+    entry(
+        "WSL_CLIP_BRIDGE_ICC",
+        "keep",
+        |v| one_of(v, &["keep", "srgb", "strip", "none", ""]),
+        "keep, srgb, or strip",
+    ),
+    entry("WSL_CLIP_BRIDGE_THUMBNAIL_SIZE", "256", NUMBER, "a number"),
+    entry("WSL_CLIP_BRIDGE_OCR", "0", FLAG, "1 or 0"),
+    entry("WSL_CLIP_BRIDGE_OCR_LANG", "", ANY, ""),
+    entry("WSL_CLIP_BRIDGE_KEEP_INPUT_FORMAT", "0", FLAG, "1 or 0"),
+    entry("WSL_CLIP_BRIDGE_SVG_SIZE", "", NUMBER, "a number"),
+    entry(
+        "WSL_CLIP_BRIDGE_SENSITIVE_TTL_SECS",
+        "30",
+        NUMBER,
+        "a number",
+    ),
+    entry("WSL_CLIP_BRIDGE_WSLPATH", "0", FLAG, "1 or 0"),
+    entry("WSL_CLIP_BRIDGE_STRICT", "0", FLAG, "1 or 0"),
+];
+
+/// Set by this binary for hook commands, which may well call it again, and
+/// read only at build time.
+const NOT_SETTINGS: [&str; 5] = [
+    "WSL_CLIP_BRIDGE_VERSION",
+    "WSL_CLIP_BRIDGE_EVENT",
+    "WSL_CLIP_BRIDGE_MIME",
+    "WSL_CLIP_BRIDGE_SELECTION",
+    "WSL_CLIP_BRIDGE_FILE",
+];
+
+/// Opt-in behavior is switched on with `WSL_CLIP_BRIDGE_*` environment
+/// variables; there is no config file. A variable that is unset falls back
+/// to the `--profile` default, if the profile has one.
+pub fn setting(name: &str) -> Option<String> {
+    debug_assert!(
+        SETTINGS.iter().any(|s| s.name == name),
+        "{name} is not in SETTINGS"
+    );
+    env::var(name)
+        .ok()
+        .or_else(|| profile::default_for(name).map(str::to_owned))
+}
+
+pub fn env_flag(name: &str) -> bool {
+    env_bool(name) == Some(true)
+}
+
+/// A boolean variable in either direction, for the few switches that are on
+/// by default; `None` when unset or unrecognized.
+pub fn env_bool(name: &str) -> Option<bool> {
+    match setting(name)?.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+pub fn env_u64(name: &str) -> Option<u64> {
+    setting(name)?.trim().parse().ok()
+}
+
+/// Warnings for `WSL_CLIP_BRIDGE_*` variables that are misspelled or hold a
+/// value their setting does not accept; either would otherwise be silently
+/// ignored.
+pub fn problems() -> Vec<String> {
+    let vars = env::vars_os().filter_map(|(name, value)| {
+        let name = name.into_string().ok()?;
+        Some((name, value.into_string().unwrap_or_default()))
+    });
+    problems_in(vars)
+}
+
+fn problems_in(vars: impl Iterator<Item = (String, String)>) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (name, value) in vars {
+        let Some(suffix) = name.strip_prefix("WSL_CLIP_BRIDGE_") else {
+            continue;
+        };
+        if NOT_SETTINGS.contains(&name.as_str()) {
+            continue;
+        }
+        match SETTINGS.iter().find(|s| s.name == name) {
+            Some(s) if !(s.valid)(&value) => {
+                problems.push(format!("ignoring {name}={value}: expected {}", s.expected));
+            }
+            Some(_) => {}
+            None => {
+                let hint = SETTINGS
+                    .iter()
+                    .find(|s| !suffix.is_empty() && s.name.ends_with(suffix))
+                    .map(|s| format!(" (did you mean {}?)", s.name))
+                    .unwrap_or_default();
+                problems.push(format!("unknown setting {name}{hint}"));
+            }
+        }
+    }
+    problems.sort();
+    problems
+}
+
+/// `--config`: one `NAME=value  (source)` line per setting.
+pub fn show() -> i32 {
+    let mut out = String::new();
+    for s in &SETTINGS {
+        let (value, source) = resolve(s);
+        let _ = writeln!(out, "{}={value}  ({source})", s.name);
+    }
+    crate::write_stdout(out.as_bytes())
+}
+
+fn resolve(s: &Setting) -> (String, String) {
+    let from_env = env::var(s.name).ok();
+    if s.name == "WSL_CLIP_BRIDGE_PROFILE" {
+        // --profile overrides the variable.
+        return match profile::active() {
+            Some(name)
+                if from_env
+                    .as_deref()
+                    .is_some_and(|v| v.trim().eq_ignore_ascii_case(name)) =>
+            {
+                (name.to_owned(), "environment".to_owned())
+            }
+            Some(name) => (name.to_owned(), "--profile".to_owned()),
+            None => (String::new(), "default".to_owned()),
+        };
+    }
+    if let Some(value) = from_env {
+        return (value, "environment".to_owned());
+    }
+    match (profile::default_for(s.name), profile::active()) {
+        (Some(value), Some(name)) => (value.to_owned(), format!("profile {name}")),
+        _ => (s.default.to_owned(), "default".to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(vars: &[(&str, &str)]) -> Vec<String> {
+        problems_in(vars.iter().map(|&(n, v)| (n.to_owned(), v.to_owned())))
+    }
+
+    #[test]
+    fn typos_and_bad_values_are_reported() {
+        assert_eq!(
+            check(&[
+                ("WSL_CLIP_BRIDGE_TTL_SECS", "5"),
+                ("WSL_CLIP_BRIDGE_JPEG_QUALITY", "\"90"),
+                ("WSL_CLIP_BRIDGE_ICC", "SRGB"),
+                ("WSL_CLIP_BRIDGE_MAX_IMAGE_BYTES", "5000,image/webp=0"),
+                ("WSL_CLIP_BRIDGE_FILE", "/tmp/x"),
+                ("HOME", "/root"),
+            ]),
+            [
+                "ignoring WSL_CLIP_BRIDGE_JPEG_QUALITY=\"90: expected 1 to 100",
+                "unknown setting WSL_CLIP_BRIDGE_TTL_SECS \
+                 (did you mean WSL_CLIP_BRIDGE_SENSITIVE_TTL_SECS?)",
+            ]
+        );
+        assert!(check(&[("WSL_CLIP_BRIDGE_PROFILE", "tiny")])[0].contains("claude"));
+    }
+}
@@ -0,0 +1,457 @@
+//! Text payload transforms applied between `wl-paste` and stdout.
+
+/// Strip the Windows `CF_HTML` envelope (`Version:`/`StartHTML:`/... header
+/// lines plus byte offsets) that HTML copied on Windows can still carry when
+/// it surfaces through `WSLg`. Anything that is not `CF_HTML` is returned
+/// unchanged.
+///
+/// The `StartHTML`/`EndHTML` document is preferred, then the fragment
+/// markers; if neither pair of offsets is usable (some bridges re-encode the
+/// body and shift them), everything after the header is returned.
+pub fn strip_cf_html(data: &[u8]) -> &[u8] {
+    if !data.starts_with(b"Version:") {
+        return data;
+    }
+
+    let mut start_html = None;
+    let mut end_html = None;
+    let mut start_fragment = None;
+    let mut end_fragment = None;
+    let mut body_at = data.len();
+    let mut pos = 0;
+    while pos < data.len() {
+        let line_end = data[pos..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(data.len(), |i| pos + i + 1);
+        let line = String::from_utf8_lossy(&data[pos..line_end]);
+        let Some((key, value)) = line.trim_end().split_once(':') else {
+            body_at = pos;
+            break;
+        };
+        if key.starts_with('<') || !key.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            body_at = pos;
+            break;
+        }
+        let offset = value.trim().parse::<usize>().ok();
+        match key {
+            "StartHTML" => start_html = offset,
+            "EndHTML" => end_html = offset,
+            "StartFragment" => start_fragment = offset,
+            "EndFragment" => end_fragment = offset,
+            _ => {}
+        }
+        pos = line_end;
+    }
+
+    let span = |start: Option<usize>, end: Option<usize>| match (start, end) {
+        (Some(s), Some(e)) if body_at <= s && s <= e && e <= data.len() => Some(&data[s..e]),
+        _ => None,
+    };
+    span(start_html, end_html)
+        .or_else(|| span(start_fragment, end_fragment))
+        .unwrap_or_else(|| &data[body_at..])
+}
+
+/// A plain-text rendering of an HTML document or fragment, for callers that
+/// ask for `text/plain` when the clipboard only offers `text/html`. Tags are
+/// dropped (block-level ones become line breaks), `<script>`/`<style>`
+/// bodies are skipped, whitespace is collapsed outside `<pre>`, and the
+/// common character references are decoded.
+pub fn html_to_text(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    let mut pre = 0usize;
+    while let Some(lt) = rest.find('<') {
+        push_html_text(&mut out, &rest[..lt], pre > 0);
+        rest = &rest[lt..];
+        let Some(gt) = rest.find('>') else {
+            // An unterminated tag: not markup after all.
+            push_html_text(&mut out, rest, pre > 0);
+            rest = "";
+            break;
+        };
+        let tag = &rest[1..gt];
+        rest = &rest[gt + 1..];
+        let closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_ascii_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match name.as_str() {
+            "script" | "style" if !closing => {
+                let end = format!("</{name}");
+                let at = rest.to_ascii_lowercase().find(&end).unwrap_or(rest.len());
+                rest = &rest[at..];
+            }
+            "pre" if closing => pre = pre.saturating_sub(1),
+            "pre" => pre += 1,
+            _ => {}
+        }
+        if matches!(
+            name.as_str(),
+            "br" | "p"
+                | "div"
+                | "li"
+                | "tr"
+                | "pre"
+                | "h1"
+                | "h2"
+                | "h3"
+                | "h4"
+                | "h5"
+                | "h6"
+                | "table"
+                | "ul"
+                | "ol"
+                | "blockquote"
+        ) && !out.is_empty()
+            && !out.ends_with('\n')
+        {
+            out.push('\n');
+        }
+        if closing && (name == "td" || name == "th") {
+            out.push('\t');
+        }
+    }
+    push_html_text(&mut out, rest, pre > 0);
+    let lines: Vec<&str> = out.lines().map(str::trim_end).collect();
+    lines.join("\n").trim_matches('\n').to_owned()
+}
+
+/// Append a run of HTML character data, decoding references and, outside
+/// `<pre>`, collapsing whitespace the way a browser renders it.
+fn push_html_text(out: &mut String, text: &str, pre: bool) {
+    let mut rest = text;
+    while !rest.is_empty() {
+        let (ch, len) = rest
+            .strip_prefix('&')
+            .and_then(decode_reference)
+            .map_or_else(
+                || {
+                    let ch = rest.chars().next().unwrap_or_default();
+                    (ch, ch.len_utf8())
+                },
+                |(ch, len)| (ch, len + 1),
+            );
+        rest = &rest[len..];
+        if !pre && ch.is_whitespace() && ch != '\u{a0}' {
+            if !out.is_empty() && !out.ends_with([' ', '\n', '\t']) {
+                out.push(' ');
+            }
+        } else if ch == '\u{a0}' {
+            out.push(' ');
+        } else {
+            out.push(ch);
+        }
+    }
+}
+
+/// Decode the character reference at the start of `s` (just after `&`),
+/// returning the character and the bytes consumed including `;`.
+fn decode_reference(s: &str) -> Option<(char, usize)> {
+    let end = s.get(..12).unwrap_or(s).find(';')?;
+    let name = &s[..end];
+    let ch = match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        _ => {
+            let num = name.strip_prefix('#')?;
+            let code = match num.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => num.parse().ok()?,
+            };
+            char::from_u32(code)?
+        }
+    };
+    Some((ch, end + 1))
+}
+
+/// The paths in a `text/uri-list` (RFC 2483), one per line, for callers
+/// that ask for `text/plain` when a file manager copied files. `file:` URIs
+/// become percent-decoded local paths; other URIs are kept verbatim and
+/// comment lines are dropped.
+pub fn uri_list_to_paths(list: &str) -> String {
+    list.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|uri| {
+            uri.strip_prefix("file://")
+                .map(|rest| rest.strip_prefix("localhost").unwrap_or(rest))
+                .filter(|path| path.starts_with('/'))
+                .map_or_else(|| uri.to_owned(), percent_decode)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The URIs of an `x-special/gnome-copied-files` payload (`copy` or `cut`
+/// on the first line, one URI per line after it) as a CRLF-separated
+/// `text/uri-list`, or `None` if it is not one.
+pub fn gnome_files_to_uri_list(files: &str) -> Option<String> {
+    let mut lines = files.lines();
+    if !matches!(lines.next()?.trim(), "copy" | "cut") {
+        return None;
+    }
+    let uris: Vec<&str> = lines.map(str::trim).filter(|l| !l.is_empty()).collect();
+    (!uris.is_empty()).then(|| uris.join("\r\n") + "\r\n")
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Rewrite every line that consists solely of a Windows path (`C:\...` or
+/// `\\server\share\...`, optionally double-quoted as Explorer's "Copy as
+/// path" emits) using `convert`. Quoting and line endings are kept; lines
+/// that are not a bare path, or that `convert` rejects, pass through.
+pub fn rewrite_windows_paths(
+    text: &str,
+    mut convert: impl FnMut(&str) -> Option<String>,
+) -> String {
+    let mut out = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        let body = line.trim_end_matches(['\r', '\n']);
+        let ending = &line[body.len()..];
+        let trimmed = body.trim();
+        let unquoted = trimmed.strip_prefix('"').and_then(|t| t.strip_suffix('"'));
+        let path = unquoted.unwrap_or(trimmed);
+        match is_windows_path(path).then(|| convert(path)).flatten() {
+            Some(linux) => {
+                let quote = if unquoted.is_some() { "\"" } else { "" };
+                out.push_str(quote);
+                out.push_str(&linux);
+                out.push_str(quote);
+            }
+            None => out.push_str(body),
+        }
+        out.push_str(ending);
+    }
+    out
+}
+
+fn is_windows_path(s: &str) -> bool {
+    let b = s.as_bytes();
+    let drive = b.len() >= 3 && b[0].is_ascii_alphabetic() && b[1] == b':' && b[2] == b'\\';
+    let unc = s.starts_with("\\\\") && s.len() > 2 && !s[2..].starts_with('\\');
+    (drive || unc) && !s.contains(['\n', '"', '<', '>', '|', '?', '*'])
+}
+
+/// A text encoding named in a `charset=` parameter that we convert to and
+/// from UTF-8.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Charset {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+}
+
+impl Charset {
+    /// The `charset` parameter of `mime`, if it names an encoding we know.
+    /// Plain `utf-16` means little-endian, as Windows writes it.
+    pub fn of(mime: &str) -> Option<Self> {
+        let name = mime.split(';').skip(1).find_map(|param| {
+            let (key, value) = param.split_once('=')?;
+            key.trim()
+                .eq_ignore_ascii_case("charset")
+                .then(|| value.trim().trim_matches('"').to_ascii_lowercase())
+        })?;
+        match name.as_str() {
+            "utf-8" | "utf8" => Some(Self::Utf8),
+            "utf-16" | "utf-16le" | "utf16" | "unicode" => Some(Self::Utf16Le),
+            "utf-16be" => Some(Self::Utf16Be),
+            "iso-8859-1" | "iso8859-1" | "latin1" | "latin-1" => Some(Self::Latin1),
+            _ => None,
+        }
+    }
+}
+
+/// Whether `data` looks like BOM-marked UTF-16: a byte-order mark and a
+/// whole number of code units.
+pub fn has_utf16_bom(data: &[u8]) -> bool {
+    (data.starts_with(b"\xff\xfe") || data.starts_with(b"\xfe\xff")) && data.len().is_multiple_of(2)
+}
+
+/// Decode `data` to UTF-8. A byte-order mark overrides `charset` and is
+/// dropped; invalid sequences become U+FFFD.
+pub fn to_utf8(data: &[u8], charset: Charset) -> String {
+    if let Some(rest) = data.strip_prefix(b"\xff\xfe") {
+        return utf16(rest, u16::from_le_bytes);
+    }
+    if let Some(rest) = data.strip_prefix(b"\xfe\xff") {
+        return utf16(rest, u16::from_be_bytes);
+    }
+    let data = data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data);
+    match charset {
+        Charset::Utf8 => String::from_utf8_lossy(data).into_owned(),
+        Charset::Utf16Le => utf16(data, u16::from_le_bytes),
+        Charset::Utf16Be => utf16(data, u16::from_be_bytes),
+        Charset::Latin1 => data.iter().map(|&b| char::from(b)).collect(),
+    }
+}
+
+fn utf16(data: &[u8], unit: fn([u8; 2]) -> u16) -> String {
+    let units = data.chunks_exact(2).map(|pair| unit([pair[0], pair[1]]));
+    char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+/// Encode `text` as `charset`, without a byte-order mark. Characters
+/// Latin-1 cannot hold become `?`.
+pub fn from_utf8(text: &str, charset: Charset) -> Vec<u8> {
+    match charset {
+        Charset::Utf8 => text.as_bytes().to_vec(),
+        Charset::Utf16Le => text.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+        Charset::Utf16Be => text.encode_utf16().flat_map(u16::to_be_bytes).collect(),
+        Charset::Latin1 => text
+            .chars()
+            .map(|c| u8::try_from(c).unwrap_or(b'?'))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cf_html(body: &str, fragment: &str) -> Vec<u8> {
+        let header_len = "Version:0.9\r\n".len()
+            + "StartHTML:EndHTML:StartFragment:EndFragment:".len()
+            + 4 * "0000000000\r\n".len();
+        let start_fragment = header_len + body.find(fragment).unwrap();
+        format!(
+            "Version:0.9\r\nStartHTML:{:010}\r\nEndHTML:{:010}\r\nStartFragment:{:010}\r\nEndFragment:{:010}\r\n{body}",
+            header_len,
+            header_len + body.len(),
+            start_fragment,
+            start_fragment + fragment.len(),
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn cf_html_yields_the_html_document() {
+        let body = "<html><body><!--StartFragment--><b>hi</b><!--EndFragment--></body></html>";
+        let data = cf_html(body, "<b>hi</b>");
+        assert_eq!(strip_cf_html(&data), body.as_bytes());
+    }
+
+    #[test]
+    fn cf_html_with_bad_offsets_falls_back_to_body() {
+        let data = b"Version:0.9\r\nStartHTML:-1\r\nEndHTML:-1\r\n<b>hi</b>";
+        assert_eq!(strip_cf_html(data), b"<b>hi</b>");
+    }
+
+    #[test]
+    fn plain_html_is_left_alone() {
+        assert_eq!(strip_cf_html(b"<b>hi</b>"), b"<b>hi</b>");
+    }
+
+    #[test]
+    fn html_renders_as_plain_text() {
+        let html = "<style>p{}</style><p>Hello&nbsp;<b>big</b>\n  world</p>\
+                    <ul><li>a &amp; b</li><li>&#x263A;</li></ul><pre>x\n  y</pre>";
+        assert_eq!(
+            html_to_text(html),
+            "Hello big world\na & b\n\u{263a}\nx\n  y"
+        );
+        assert_eq!(html_to_text("1 < 2 &bogus; ok"), "1 < 2 &bogus; ok");
+    }
+
+    #[test]
+    fn uri_lists_become_paths() {
+        let list = "# copied\r\nfile:///home/me/My%20File.txt\r\nfile://localhost/tmp/%C3%A9\r\nhttps://example.com/a%20b\r\n";
+        assert_eq!(
+            uri_list_to_paths(list),
+            "/home/me/My File.txt\n/tmp/\u{e9}\nhttps://example.com/a%20b"
+        );
+    }
+
+    #[test]
+    fn gnome_file_lists_become_uri_lists() {
+        assert_eq!(
+            gnome_files_to_uri_list("cut\nfile:///tmp/a\nfile:///tmp/b%20c").as_deref(),
+            Some("file:///tmp/a\r\nfile:///tmp/b%20c\r\n")
+        );
+        assert_eq!(gnome_files_to_uri_list("copy\n"), None);
+        assert_eq!(gnome_files_to_uri_list("file:///tmp/a"), None);
+    }
+
+    #[test]
+    fn windows_path_lines_are_rewritten() {
+        let convert = |p: &str| Some(format!("/mnt/c/{}", p[3..].replace('\\', "/")));
+        let input = "\"C:\\Users\\me\\My File.txt\"\r\nC:\\tmp\r\nsee C:\\tmp here\n";
+        assert_eq!(
+            rewrite_windows_paths(input, convert),
+            "\"/mnt/c/Users/me/My File.txt\"\r\n/mnt/c/tmp\r\nsee C:\\tmp here\n"
+        );
+    }
+
+    #[test]
+    fn rejected_or_non_paths_pass_through() {
+        assert_eq!(rewrite_windows_paths("C:\\tmp", |_| None), "C:\\tmp");
+        assert_eq!(
+            rewrite_windows_paths("C:relative", |_| unreachable!()),
+            "C:relative"
+        );
+        assert!(is_windows_path("\\\\server\\share\\x"));
+    }
+
+    #[test]
+    fn charsets_convert_to_and_from_utf8() {
+        assert_eq!(
+            Charset::of("text/plain; charset=\"UTF-16\""),
+            Some(Charset::Utf16Le)
+        );
+        assert_eq!(
+            Charset::of("text/plain;charset=latin1"),
+            Some(Charset::Latin1)
+        );
+        assert_eq!(Charset::of("text/plain;charset=koi8-r"), None);
+        assert_eq!(Charset::of("text/plain"), None);
+
+        assert_eq!(to_utf8(b"\xff\xfeh\0\xe9\0", Charset::Utf8), "h\u{e9}");
+        assert_eq!(
+            to_utf8(b"\xfe\xff\0h\xd8\x3d\xde\x00", Charset::Utf8),
+            "h\u{1f600}"
+        );
+        assert_eq!(to_utf8(b"caf\xe9", Charset::Latin1), "caf\u{e9}");
+        assert_eq!(to_utf8(b"\0\xd8", Charset::Utf16Le), "\u{fffd}");
+
+        for charset in [Charset::Utf8, Charset::Utf16Le, Charset::Utf16Be] {
+            let text = "caf\u{e9} \u{1f600}";
+            assert_eq!(to_utf8(&from_utf8(text, charset), charset), text);
+        }
+        assert_eq!(
+            from_utf8("caf\u{e9} \u{1f600}", Charset::Latin1),
+            b"caf\xe9 ?"
+        );
+    }
+}
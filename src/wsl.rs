@@ -0,0 +1,182 @@
+//! Detection of the WSL environment the shim is running in.
+//!
+//! Done once per process from `/proc`, the environment, and the filesystem,
+//! so a missing backend is reported up front instead of surfacing as a
+//! failed spawn, and no `which`-style subprocess is ever needed.
+
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// `WSLg`'s own runtime directory. Distros running systemd point
+/// `XDG_RUNTIME_DIR` at `/run/user/<uid>` instead, which `WSLg` does not
+/// always populate.
+const WSLG_RUNTIME_DIR: &str = "/mnt/wslg/runtime-dir";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kernel {
+    /// Not a WSL kernel: plain Linux, where `wl-paste` may work natively.
+    Native,
+    Wsl1,
+    Wsl2,
+}
+
+#[derive(Debug)]
+pub struct Environment {
+    pub kernel: Kernel,
+    /// `WSLg`'s shared mount, which carries the Wayland socket.
+    pub wslg: bool,
+    /// The compositor socket `wl-paste` will connect to, if one exists.
+    pub wayland_socket: Option<PathBuf>,
+    /// Whether `wl-paste` is on `PATH` (wl-clipboard ships it together with
+    /// `wl-copy`).
+    pub wl_clipboard: bool,
+}
+
+pub fn environment() -> &'static Environment {
+    static ENV: OnceLock<Environment> = OnceLock::new();
+    ENV.get_or_init(|| Environment {
+        kernel: fs::read_to_string("/proc/sys/kernel/osrelease")
+            .map_or(Kernel::Native, |release| kernel_from_release(&release)),
+        wslg: Path::new("/mnt/wslg").is_dir(),
+        wayland_socket: socket_candidates(
+            env::var_os("WAYLAND_DISPLAY").as_deref(),
+            env::var_os("XDG_RUNTIME_DIR").as_deref(),
+        )
+        .into_iter()
+        .find(|path| fs::metadata(path).is_ok_and(|m| m.file_type().is_socket())),
+        wl_clipboard: find_in_path("wl-paste").is_some(),
+    })
+}
+
+fn kernel_from_release(release: &str) -> Kernel {
+    let release = release.to_ascii_lowercase();
+    if release.contains("wsl2") {
+        Kernel::Wsl2
+    } else if release.contains("microsoft") {
+        Kernel::Wsl1
+    } else {
+        Kernel::Native
+    }
+}
+
+/// Why the `wl-paste` backend cannot work here, or `None` if it is worth
+/// trying.
+pub fn wayland_unavailable() -> Option<&'static str> {
+    unavailable_in(environment())
+}
+
+const fn unavailable_in(env: &Environment) -> Option<&'static str> {
+    match env.kernel {
+        Kernel::Wsl1 => Some(
+            "WSL1 has no WSLg clipboard; convert the distro with `wsl --set-version <distro> 2`",
+        ),
+        Kernel::Wsl2 if !env.wslg => Some(
+            "WSLg is not running (/mnt/wslg missing); check guiApplications=true in .wslconfig",
+        ),
+        _ if env.wayland_socket.is_none() => Some(
+            "no Wayland socket found ($WAYLAND_DISPLAY under $XDG_RUNTIME_DIR or /mnt/wslg/runtime-dir)",
+        ),
+        _ if !env.wl_clipboard => Some(
+            "wl-paste not found on PATH; install wl-clipboard (e.g. `apt install wl-clipboard`)",
+        ),
+        _ => None,
+    }
+}
+
+/// Where libwayland would look for the compositor socket, in order, with
+/// `WSLg`'s runtime directory as a last resort. An absolute
+/// `WAYLAND_DISPLAY` is used as is.
+fn socket_candidates(display: Option<&OsStr>, runtime_dir: Option<&OsStr>) -> Vec<PathBuf> {
+    let name = Path::new(
+        display
+            .filter(|d| !d.is_empty())
+            .unwrap_or_else(|| OsStr::new("wayland-0")),
+    );
+    if name.is_absolute() {
+        return vec![name.to_path_buf()];
+    }
+    let mut candidates: Vec<PathBuf> = runtime_dir
+        .filter(|d| !d.is_empty())
+        .map(|dir| Path::new(dir).join(name))
+        .into_iter()
+        .collect();
+    candidates.push(Path::new(WSLG_RUNTIME_DIR).join(name));
+    candidates
+}
+
+/// Resolve `program` against `PATH` the way `execvp` would.
+pub fn find_in_path(program: &str) -> Option<PathBuf> {
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(program))
+        .find(|path| fs::metadata(path).is_ok_and(|m| m.is_file()))
+}
+
+/// Use the X11 backend instead of `wl-paste`: an X server is reachable but
+/// Wayland is not (VcXsrv/X410 users, WSL1, or `WSLg` disabled). An unset
+/// `WAYLAND_DISPLAY` alone does not count: the socket is looked for anyway.
+#[cfg(feature = "x11")]
+pub fn prefer_x11() -> bool {
+    x11_preferred(env::var_os("DISPLAY").as_deref(), environment())
+}
+
+#[cfg(feature = "x11")]
+fn x11_preferred(display: Option<&OsStr>, env: &Environment) -> bool {
+    display.is_some_and(|d| !d.is_empty()) && unavailable_in(env).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kernel_release_strings() {
+        assert_eq!(
+            kernel_from_release("5.15.167.4-microsoft-standard-WSL2\n"),
+            Kernel::Wsl2
+        );
+        assert_eq!(kernel_from_release("4.4.0-19041-Microsoft\n"), Kernel::Wsl1);
+        assert_eq!(kernel_from_release("6.8.0-45-generic\n"), Kernel::Native);
+    }
+
+    #[test]
+    fn socket_lookup_order() {
+        let os = |s| Some(OsStr::new(s));
+        assert_eq!(
+            socket_candidates(None, os("/run/user/1000")),
+            [
+                PathBuf::from("/run/user/1000/wayland-0"),
+                PathBuf::from("/mnt/wslg/runtime-dir/wayland-0"),
+            ]
+        );
+        assert_eq!(
+            socket_candidates(os("wayland-1"), None),
+            [PathBuf::from("/mnt/wslg/runtime-dir/wayland-1")]
+        );
+        assert_eq!(
+            socket_candidates(os("/tmp/wl.sock"), os("/run/user/1000")),
+            [PathBuf::from("/tmp/wl.sock")]
+        );
+    }
+
+    /// `WSLg` with `WAYLAND_DISPLAY` unset but `DISPLAY` set (a common
+    /// systemd setup): the discovered `wayland-0` socket must win over X11.
+    #[cfg(feature = "x11")]
+    #[test]
+    fn discovered_socket_beats_x11() {
+        let display = Some(OsStr::new(":0"));
+        let mut env = Environment {
+            kernel: Kernel::Wsl2,
+            wslg: true,
+            wayland_socket: Some(PathBuf::from("/mnt/wslg/runtime-dir/wayland-0")),
+            wl_clipboard: true,
+        };
+        assert!(!x11_preferred(display, &env));
+        env.wayland_socket = None;
+        assert!(x11_preferred(display, &env));
+        assert!(!x11_preferred(None, &env));
+    }
+}
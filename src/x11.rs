@@ -0,0 +1,250 @@
+//! X11 clipboard backend for setups with an X server but no Wayland
+//! compositor (`VcXsrv`, X410, WSL1). Speaks the ICCCM selection protocol
+//! directly via `x11rb`, including INCR transfers for large payloads.
+
+use std::io;
+use std::os::fd::AsFd;
+use std::time::{Duration, Instant};
+
+use rustix::event::{PollFd, PollFlags, Timespec, poll};
+use x11rb::CURRENT_TIME;
+use x11rb::connection::Connection;
+use x11rb::protocol::Event;
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ConnectionExt as _, CreateWindowAux, EventMask, GetPropertyReply, Property,
+    Window, WindowClass,
+};
+use x11rb::rust_connection::RustConnection;
+
+use crate::Selection;
+
+/// Names the X11 world uses for plain text, in order of preference.
+const TEXT_TARGETS: [&str; 2] = ["UTF8_STRING", "STRING"];
+
+pub fn list_types(sel: Selection, timeout: Duration) -> io::Result<Vec<String>> {
+    let session = Session::open(sel)?;
+    let data = session.read(session.atom("TARGETS")?, timeout)?;
+    let names = atoms(&data)
+        .map(|atom| session.atom_name(atom))
+        .collect::<io::Result<Vec<_>>>()?;
+    Ok(offered_types(names))
+}
+
+/// The atoms in a `TARGETS` reply: 32-bit values in the server's byte
+/// order, which x11rb has already swapped to ours.
+fn atoms(data: &[u8]) -> impl Iterator<Item = Atom> + '_ {
+    data.chunks_exact(4)
+        .map(|c| u32::from_ne_bytes([c[0], c[1], c[2], c[3]]))
+}
+
+/// Target names as MIME types: X11's text targets also answer to
+/// `text/plain`, listed once before the first of them.
+fn offered_types(names: Vec<String>) -> Vec<String> {
+    let mut types = Vec::with_capacity(names.len() + 1);
+    for name in names {
+        if TEXT_TARGETS.contains(&name.as_str()) && !types.iter().any(|t| t == "text/plain") {
+            types.push("text/plain".to_owned());
+        }
+        types.push(name);
+    }
+    types
+}
+
+pub fn fetch(mime: &str, sel: Selection, timeout: Duration) -> io::Result<Vec<u8>> {
+    let session = Session::open(sel)?;
+    let first = session.read(session.atom(mime)?, timeout);
+    if first.is_ok() || !crate::same_base_mime(mime, "text/plain") {
+        return first;
+    }
+    for name in TEXT_TARGETS {
+        if let Ok(data) = session.read(session.atom(name)?, timeout) {
+            return Ok(data);
+        }
+    }
+    first
+}
+
+struct Session {
+    conn: RustConnection,
+    window: Window,
+    selection: Atom,
+    property: Atom,
+    incr: Atom,
+}
+
+impl Session {
+    fn open(sel: Selection) -> io::Result<Self> {
+        let (conn, screen_num) = x11rb::connect(None).map_err(io::Error::other)?;
+        let screen = &conn.setup().roots[screen_num];
+        let window = conn.generate_id().map_err(io::Error::other)?;
+        conn.create_window(
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            window,
+            screen.root,
+            0,
+            0,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            screen.root_visual,
+            &CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+        )
+        .map_err(io::Error::other)?;
+        let mut session = Self {
+            conn,
+            window,
+            selection: 0,
+            property: 0,
+            incr: 0,
+        };
+        session.selection = match sel {
+            Selection::Clipboard => session.atom("CLIPBOARD")?,
+            Selection::Primary => AtomEnum::PRIMARY.into(),
+        };
+        session.property = session.atom("WSL_CLIP_BRIDGE")?;
+        session.incr = session.atom("INCR")?;
+        Ok(session)
+    }
+
+    fn atom(&self, name: &str) -> io::Result<Atom> {
+        Ok(self
+            .conn
+            .intern_atom(false, name.as_bytes())
+            .map_err(io::Error::other)?
+            .reply()
+            .map_err(io::Error::other)?
+            .atom)
+    }
+
+    fn atom_name(&self, atom: Atom) -> io::Result<String> {
+        let reply = self
+            .conn
+            .get_atom_name(atom)
+            .map_err(io::Error::other)?
+            .reply()
+            .map_err(io::Error::other)?;
+        Ok(String::from_utf8_lossy(&reply.name).into_owned())
+    }
+
+    /// Ask the selection owner to convert to `target` and collect the
+    /// result, following the INCR protocol when the owner chunks it.
+    fn read(&self, target: Atom, timeout: Duration) -> io::Result<Vec<u8>> {
+        // No deadline when the timeout is too far off to represent.
+        let deadline = Instant::now().checked_add(timeout);
+        self.conn
+            .convert_selection(
+                self.window,
+                self.selection,
+                target,
+                self.property,
+                CURRENT_TIME,
+            )
+            .map_err(io::Error::other)?;
+        self.conn.flush().map_err(io::Error::other)?;
+
+        loop {
+            match self.next_event(deadline)? {
+                Event::SelectionNotify(ev) if ev.property == u32::from(AtomEnum::NONE) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        "clipboard owner cannot provide that type",
+                    ));
+                }
+                Event::SelectionNotify(_) => break,
+                _ => {}
+            }
+        }
+
+        let first = self.take_property()?;
+        if first.type_ != self.incr {
+            return Ok(first.value);
+        }
+        // INCR: every chunk arrives as a new value of our property; an empty
+        // one marks the end.
+        let mut data = Vec::new();
+        loop {
+            match self.next_event(deadline)? {
+                Event::PropertyNotify(ev)
+                    if ev.atom == self.property && ev.state == Property::NEW_VALUE =>
+                {
+                    let chunk = self.take_property()?;
+                    if chunk.value.is_empty() {
+                        return Ok(data);
+                    }
+                    data.extend_from_slice(&chunk.value);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn take_property(&self) -> io::Result<GetPropertyReply> {
+        let reply = self
+            .conn
+            .get_property(true, self.window, self.property, AtomEnum::ANY, 0, u32::MAX)
+            .map_err(io::Error::other)?
+            .reply()
+            .map_err(io::Error::other)?;
+        self.conn.flush().map_err(io::Error::other)?;
+        Ok(reply)
+    }
+
+    /// The next event, blocking on the connection until one arrives or
+    /// `deadline`, if any, passes.
+    fn next_event(&self, deadline: Option<Instant>) -> io::Result<Event> {
+        loop {
+            // Also drains whatever the socket already holds, so the poll
+            // below only waits for bytes not yet read.
+            if let Some(event) = self.conn.poll_for_event().map_err(io::Error::other)? {
+                return Ok(event);
+            }
+            let remaining = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+            if remaining == Some(Duration::ZERO) {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "X11 selection owner did not answer in time",
+                ));
+            }
+            let timeout = remaining.and_then(|r| Timespec::try_from(r).ok());
+            let fd = self.conn.stream().as_fd();
+            match poll(&mut [PollFd::new(&fd, PollFlags::IN)], timeout.as_ref()) {
+                Ok(_) | Err(rustix::io::Errno::INTR) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn targets_reply_is_split_into_atoms() {
+        let mut data: Vec<u8> = [31u32, 287, 4]
+            .iter()
+            .flat_map(|a| a.to_ne_bytes())
+            .collect();
+        data.push(0xff);
+        assert_eq!(atoms(&data).collect::<Vec<_>>(), [31, 287, 4]);
+        assert_eq!(atoms(&[]).count(), 0);
+    }
+
+    #[test]
+    fn text_targets_are_offered_as_text_plain_once() {
+        let names = ["TARGETS", "image/png", "UTF8_STRING", "STRING"].map(str::to_owned);
+        assert_eq!(
+            offered_types(names.to_vec()),
+            [
+                "TARGETS",
+                "image/png",
+                "text/plain",
+                "UTF8_STRING",
+                "STRING"
+            ]
+        );
+        let names = ["TARGETS", "image/png"].map(str::to_owned);
+        assert_eq!(offered_types(names.to_vec()), ["TARGETS", "image/png"]);
+    }
+}